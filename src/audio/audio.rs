@@ -1,52 +1,137 @@
-use std::cell::{Cell, RefCell};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
-use jack::{AsyncClient, ClientOptions, Error};
-use jack::{AudioIn, AudioOut, Client, Port};
-use jack::{ClosureProcessHandler, Control, ProcessScope};
+use jack::{AsyncClient, ClientOptions, Error, Frames};
+use jack::{AudioIn, AudioOut, Client, Port, PortFlags};
+use jack::{Control, NotificationHandler, ProcessHandler, ProcessScope};
 
-pub(crate) type AudioCallback = Box<dyn FnMut(&mut AudioPorts, &ProcessScope) + Send + Sync>;
+use super::{AudioBackend, AudioCallback, AudioClock, AudioDeactivateFlag, AudioError};
+use super::{CallbackHandle, MixerInput, PlaybackMixer};
 
-type ClientCallback = impl Fn(&Client, &ProcessScope) -> Control + Send;
-type AsyncClientCallback = AsyncClient<(), ClosureProcessHandler<ClientCallback>>;
+type AsyncClientCallback = AsyncClient<JackNotifications, JackProcess>;
 
 const CLIENT_NAME_PREFIX: &str = "AcousticNetwork";
+const DEFAULT_CAPTURE_PORT: &str = "system:capture_1";
+const DEFAULT_PLAYBACK_PORT: &str = "system:playback_1";
+const AUDIO_PORT_TYPE_PATTERN: &str = "audio";
+
+#[derive(Debug, Clone)]
+pub struct AudioRouting {
+    pub capture: Option<String>,
+    pub playback: Option<String>,
+}
+
+impl Default for AudioRouting {
+    fn default() -> Self {
+        Self {
+            capture: Some(DEFAULT_CAPTURE_PORT.to_string()),
+            playback: Some(DEFAULT_PLAYBACK_PORT.to_string()),
+        }
+    }
+}
+
+impl AudioRouting {
+    pub fn unconnected() -> Self {
+        Self {
+            capture: None,
+            playback: None,
+        }
+    }
+}
 
 pub struct Audio {
-    client: RefCell<Option<Client>>,
-    ports: Mutex<Option<AudioPorts>>,
-    pub timetick: AtomicUsize,
-    active_client: RefCell<Option<AsyncClientCallback>>,
-    pub sample_rate: Cell<Option<usize>>,
-    callbacks: Mutex<Vec<AudioCallback>>,
+    routing: AudioRouting,
+    client: Mutex<Option<Client>>,
+    ports: Mutex<Option<JackPorts>>,
+    timetick: Arc<AtomicUsize>,
+    xruns: Arc<AtomicUsize>,
+    buffer_size: Arc<AtomicUsize>,
+    active_client: Mutex<Option<AsyncClientCallback>>,
+    sample_rate: AtomicUsize,
+    mixer: Arc<Mutex<PlaybackMixer>>,
+    mixer_input: MixerInput,
 }
 
-pub struct AudioPorts {
-    pub capture: Port<AudioIn>,
-    pub playback: Port<AudioOut>,
+struct JackPorts {
+    capture: Port<AudioIn>,
+    playback: Port<AudioOut>,
 }
 
-pub enum AudioDeactivateFlag {
-    Deactivate,
-    Restart,
-    CleanRestart,
+struct JackProcess {
+    ports: JackPorts,
+    timetick: Arc<AtomicUsize>,
+    buffer_size: Arc<AtomicUsize>,
+    mixer: Arc<Mutex<PlaybackMixer>>,
+}
+
+impl ProcessHandler for JackProcess {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        let clock = AudioClock {
+            timetick: self.timetick.load(Ordering::Relaxed),
+            frame_time: Some(ps.last_frame_time()),
+        };
+
+        let capture = self.ports.capture.as_slice(ps);
+        let playback = self.ports.playback.as_mut_slice(ps);
+
+        // The mixer is only locked elsewhere while the client is set up or
+        // torn down, the cycle never waits for it and plays silence instead.
+        match self.mixer.try_lock() {
+            Ok(mut mixer) => mixer.process(clock, capture, playback),
+            Err(_) => playback.fill(0.0),
+        }
+
+        self.timetick
+            .fetch_add(ps.n_frames() as usize, Ordering::Relaxed);
+        Control::Continue
+    }
+
+    fn buffer_size(&mut self, _: &Client, size: Frames) -> Control {
+        // Called outside the realtime cycle, so the mixer may allocate here
+        // and keep its scratch buffers untouched during processing.
+        info!("JACK buffer size set to {}", size);
+        self.buffer_size.store(size as usize, Ordering::Relaxed);
+        self.mixer.lock().unwrap().set_buffer_size(size as usize);
+        Control::Continue
+    }
+}
+
+struct JackNotifications {
+    xruns: Arc<AtomicUsize>,
+}
+
+impl NotificationHandler for JackNotifications {
+    fn xrun(&mut self, _: &Client) -> Control {
+        self.xruns.fetch_add(1, Ordering::Relaxed);
+        Control::Continue
+    }
 }
 
 impl Audio {
-    pub fn new() -> Result<&'static Audio, Error> {
+    pub fn new() -> Result<Arc<Audio>, Error> {
+        Self::with_routing(AudioRouting::default())
+    }
+
+    pub fn with_routing(routing: AudioRouting) -> Result<Arc<Audio>, Error> {
+        let mixer = PlaybackMixer::new();
+        let mixer_input = mixer.input();
+
         let audio = Audio {
-            client: RefCell::new(None),
+            routing,
+            client: Mutex::new(None),
             ports: Mutex::new(None),
-            timetick: AtomicUsize::new(0),
-            active_client: RefCell::new(None),
-            sample_rate: Cell::new(None),
-            callbacks: Mutex::new(Vec::new()),
+            timetick: Arc::new(AtomicUsize::new(0)),
+            xruns: Arc::new(AtomicUsize::new(0)),
+            buffer_size: Arc::new(AtomicUsize::new(0)),
+            active_client: Mutex::new(None),
+            sample_rate: AtomicUsize::new(0),
+            mixer: Arc::new(Mutex::new(mixer)),
+            mixer_input,
         };
 
         audio.init_client()?;
 
-        Ok(Box::leak(Box::new(audio)))
+        Ok(Arc::new(audio))
     }
 
     pub fn init_client(&self) -> Result<(), Error> {
@@ -59,69 +144,104 @@ impl Audio {
         let out_port = client.register_port("output", AudioOut::default())?;
 
         let sample_rate = client.sample_rate();
-        *self.client.borrow_mut() = Some(client);
+        let buffer_size = client.buffer_size() as usize;
+        *self.client.lock().unwrap() = Some(client);
 
-        let ports = AudioPorts {
+        let ports = JackPorts {
             capture: in_port,
             playback: out_port,
         };
 
         *self.ports.lock().unwrap() = Some(ports);
-        self.sample_rate.set(Some(sample_rate));
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+        self.buffer_size.store(buffer_size, Ordering::Relaxed);
+        self.mixer.lock().unwrap().set_buffer_size(buffer_size);
         self.timetick.store(0, Ordering::Relaxed);
+        self.xruns.store(0, Ordering::Relaxed);
 
         Ok(())
     }
 
-    pub fn register(&'static self, callback: AudioCallback) {
-        self.callbacks.lock().unwrap().push(callback);
+    fn find_port(client: &Client, pattern: &str, flags: PortFlags) -> Result<String, AudioError> {
+        if client.port_by_name(pattern).is_some() {
+            return Ok(pattern.to_string());
+        }
+
+        client
+            .ports(Some(pattern), Some(AUDIO_PORT_TYPE_PATTERN), flags)
+            .into_iter()
+            .next()
+            .ok_or_else(|| AudioError::PortNotFound(pattern.to_string()))
     }
+}
 
-    pub fn activate(&'static self) {
-        let Self {
-            ports,
-            timetick,
-            callbacks,
-            ..
-        } = self;
+impl AudioBackend for Audio {
+    fn register_with_gain(&self, callback: AudioCallback, gain: f32) -> CallbackHandle {
+        self.mixer_input.add(callback, gain)
+    }
 
-        let client = self.client.borrow_mut().take();
-        let buffer_size = client.as_ref().unwrap().buffer_size();
+    fn activate(&self) -> Result<(), AudioError> {
+        let routing = &self.routing;
 
-        let process_callback = move |_: &Client, ps: &ProcessScope| -> Control {
-            let mut ports = ports.lock().unwrap();
+        let mut client = self.client.lock().unwrap();
 
-            let mut callbacks = callbacks.lock().unwrap();
-            for callback in callbacks.iter_mut() {
-                callback(&mut ports.as_mut().unwrap(), ps);
-            }
+        let (capture_port, playback_port) = {
+            let client = client.as_ref().unwrap();
+
+            let capture_port = routing
+                .capture
+                .as_deref()
+                .map(|pattern| Self::find_port(client, pattern, PortFlags::IS_OUTPUT))
+                .transpose()?;
+            let playback_port = routing
+                .playback
+                .as_deref()
+                .map(|pattern| Self::find_port(client, pattern, PortFlags::IS_INPUT))
+                .transpose()?;
+
+            (capture_port, playback_port)
+        };
+
+        let notifications = JackNotifications {
+            xruns: self.xruns.clone(),
+        };
+        // The process handler owns the ports, only their names stay here.
+        let ports = self.ports.lock().unwrap().take().unwrap();
+        let own_capture_port = ports.capture.name();
+        let own_playback_port = ports.playback.name();
 
-            timetick.fetch_add(buffer_size as usize, Ordering::Relaxed);
-            Control::Continue
+        let process = JackProcess {
+            ports,
+            timetick: self.timetick.clone(),
+            buffer_size: self.buffer_size.clone(),
+            mixer: self.mixer.clone(),
         };
 
-        let process = ClosureProcessHandler::new(process_callback);
-        let active_client = client.unwrap().activate_async((), process).unwrap();
+        let client = client.take().unwrap();
+        let active_client = client.activate_async(notifications, process)?;
 
-        {
+        let connect_result = {
             let client = active_client.as_client();
 
-            let capture_port = client.port_by_name("system:capture_1").unwrap();
-            let playback_port = client.port_by_name("system:playback_1").unwrap();
+            let capture_result = capture_port.map_or(Ok(()), |capture_port| {
+                let own_port = own_capture_port?;
+                client.connect_ports_by_name(&capture_port, &own_port)
+            });
 
-            if let Some(ports) = ports.lock().unwrap().as_ref() {
-                client.connect_ports(&capture_port, &ports.capture).unwrap();
-                client
-                    .connect_ports(&ports.playback, &playback_port)
-                    .unwrap();
-            };
-        }
+            let playback_result = playback_port.map_or(Ok(()), |playback_port| {
+                let own_port = own_playback_port?;
+                client.connect_ports_by_name(&own_port, &playback_port)
+            });
+
+            capture_result.and(playback_result)
+        };
 
-        *self.active_client.borrow_mut() = Some(active_client);
+        *self.active_client.lock().unwrap() = Some(active_client);
+        connect_result.map_err(AudioError::from)
     }
 
-    pub fn deactivate(&self, flag: AudioDeactivateFlag) {
-        let client = self.active_client.take().unwrap();
+    fn deactivate(&self, flag: AudioDeactivateFlag) {
+        let client = self.active_client.lock().unwrap().take().unwrap();
         client.deactivate().unwrap();
 
         match flag {
@@ -129,10 +249,38 @@ impl Audio {
                 self.init_client().unwrap();
             }
             AudioDeactivateFlag::CleanRestart => {
-                self.callbacks.lock().unwrap().clear();
+                self.mixer.lock().unwrap().clear();
                 self.init_client().unwrap();
             }
             AudioDeactivateFlag::Deactivate => {}
         }
     }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    fn timetick(&self) -> usize {
+        self.timetick.load(Ordering::Relaxed)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.buffer_size.load(Ordering::Relaxed)
+    }
+
+    fn xruns(&self) -> usize {
+        self.xruns.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Audio {
+    fn drop(&mut self) {
+        if let Some(client) = self.active_client.lock().unwrap().take() {
+            if let Err(error) = client.deactivate() {
+                error!("Failed to deactivate audio client: {}", error);
+            }
+        }
+
+        self.ports.lock().unwrap().take();
+    }
 }
@@ -0,0 +1,61 @@
+use std::fmt;
+
+use super::CallbackHandle;
+
+pub type AudioCallback = Box<dyn FnMut(&mut AudioPorts) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AudioClock {
+    pub timetick: usize,
+    pub frame_time: Option<u32>,
+}
+
+pub struct AudioPorts<'a> {
+    pub clock: AudioClock,
+    pub capture: &'a [f32],
+    pub playback: &'a mut [f32],
+}
+
+pub enum AudioDeactivateFlag {
+    Deactivate,
+    Restart,
+    CleanRestart,
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    Jack(jack::Error),
+    PortNotFound(String),
+    Io(std::io::Error),
+}
+
+impl From<jack::Error> for AudioError {
+    fn from(error: jack::Error) -> Self {
+        Self::Jack(error)
+    }
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Jack(error) => write!(f, "JACK error: {}", error),
+            Self::PortNotFound(pattern) => write!(f, "No port matches \"{}\"", pattern),
+            Self::Io(error) => write!(f, "I/O error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+pub trait AudioBackend: Send + Sync {
+    fn register_with_gain(&self, callback: AudioCallback, gain: f32) -> CallbackHandle;
+    fn register(&self, callback: AudioCallback) -> CallbackHandle {
+        self.register_with_gain(callback, 1.0)
+    }
+    fn activate(&self) -> Result<(), AudioError>;
+    fn deactivate(&self, flag: AudioDeactivateFlag);
+    fn sample_rate(&self) -> usize;
+    fn timetick(&self) -> usize;
+    fn buffer_size(&self) -> usize;
+    fn xruns(&self) -> usize;
+}
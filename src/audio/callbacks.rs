@@ -1,29 +1,22 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
-
-use jack::ProcessScope;
-use super::{audio::AudioCallback, AudioPorts, AudioPacket};
+use super::{AudioCallback, AudioPorts, AudioPacket};
 
 pub struct CreateCallback;
 
 impl CreateCallback {
     pub fn capture(output: AudioPacket) -> AudioCallback {
-        let capture_callback = move |ports: &mut AudioPorts, ps: &ProcessScope| {
-            output.write_chunk(ports.capture.as_slice(&ps));
+        let capture_callback = move |ports: &mut AudioPorts| {
+            output.write_chunk(ports.capture);
         };
         Box::new(capture_callback)
     }
 
-    pub fn playback(input: AudioPacket, timetick: &'static AtomicUsize) -> AudioCallback {
-        let playback_callback = move |ports: &mut AudioPorts, ps: &ProcessScope| {
-            let time = timetick.load(Ordering::Relaxed) as f32;
-            let buffer = ports.playback.as_mut_slice(&ps);
-            for (index, sample) in buffer.iter_mut().enumerate() {
-                let current_sample = (index as f32 + time) as usize;
-                *sample = match input.read_sample(current_sample) {
-                    Some(sample) => sample,
-                    None => 0.0,
-                };
+    pub fn playback(input: AudioPacket) -> AudioCallback {
+        let mut time = 0;
+        let playback_callback = move |ports: &mut AudioPorts| {
+            for (index, sample) in ports.playback.iter_mut().enumerate() {
+                *sample = input.read_sample(index + time).unwrap_or(0.0);
             }
+            time += ports.playback.len();
         };
         Box::new(playback_callback)
     }
@@ -0,0 +1,262 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+
+const SILENCE_POWER: f32 = 1e-6;
+const POWER_REFRESH_FACTOR: f32 = 0.05;
+
+#[derive(Debug, Clone)]
+pub struct ChannelProfile {
+    pub seed: u64,
+    pub snr_db: Option<f32>,
+    pub attenuation_db: f32,
+    pub delay: usize,
+    pub multipath: Vec<(usize, f32)>,
+    pub dc_offset: f32,
+    pub clipping: Option<f32>,
+    pub drift_ppm: f32,
+}
+
+impl Default for ChannelProfile {
+    fn default() -> Self {
+        Self::ideal()
+    }
+}
+
+impl ChannelProfile {
+    pub fn ideal() -> Self {
+        Self {
+            seed: 0,
+            snr_db: None,
+            attenuation_db: 0.0,
+            delay: 0,
+            multipath: Vec::new(),
+            dc_offset: 0.0,
+            clipping: None,
+            drift_ppm: 0.0,
+        }
+    }
+
+    pub fn cable() -> Self {
+        Self {
+            snr_db: Some(30.0),
+            attenuation_db: 1.0,
+            delay: 37,
+            dc_offset: 0.005,
+            drift_ppm: 5.0,
+            ..Self::ideal()
+        }
+    }
+
+    pub fn air() -> Self {
+        Self {
+            snr_db: Some(15.0),
+            attenuation_db: 12.0,
+            delay: 211,
+            multipath: vec![(7, 0.4), (23, -0.2), (61, 0.1)],
+            dc_offset: 0.01,
+            drift_ppm: 40.0,
+            ..Self::ideal()
+        }
+    }
+}
+
+pub struct ChannelSimulator {
+    profile: ChannelProfile,
+    rng: StdRng,
+    history: VecDeque<f32>,
+    signal_power: f32,
+    drift_ratio: f64,
+    drift_position: f64,
+    drift_consumed: f64,
+    last_sample: f32,
+}
+
+impl ChannelSimulator {
+    pub fn new(profile: ChannelProfile) -> Self {
+        let history_length = profile
+            .multipath
+            .iter()
+            .map(|&(delay, _)| delay)
+            .max()
+            .unwrap_or(0)
+            + profile.delay
+            + 1;
+
+        Self {
+            rng: StdRng::seed_from_u64(profile.seed),
+            history: VecDeque::from(vec![0.0; history_length]),
+            signal_power: 0.0,
+            drift_ratio: 1.0 + profile.drift_ppm as f64 * 1e-6,
+            drift_position: 0.0,
+            drift_consumed: 0.0,
+            last_sample: 0.0,
+            profile,
+        }
+    }
+
+    pub fn profile(&self) -> &ChannelProfile {
+        &self.profile
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let start = output.len();
+
+        for &sample in input {
+            self.history.pop_back();
+            self.history.push_front(sample);
+
+            let ChannelProfile {
+                delay, multipath, ..
+            } = &self.profile;
+
+            let echo = multipath
+                .iter()
+                .map(|&(tap, gain)| self.history[delay + tap] * gain)
+                .sum::<f32>();
+
+            self.resample(self.history[*delay] + echo, output);
+        }
+
+        let attenuation = 10f32.powf(-self.profile.attenuation_db / 20.0);
+        let chunk = &mut output[start..];
+        chunk.iter_mut().for_each(|sample| *sample *= attenuation);
+
+        if !chunk.is_empty() {
+            let power = chunk.iter().map(|x| x.powi(2)).sum::<f32>() / chunk.len() as f32;
+            if power > SILENCE_POWER && self.signal_power == 0.0 {
+                self.signal_power = power;
+            } else if power > SILENCE_POWER {
+                self.signal_power += (power - self.signal_power) * POWER_REFRESH_FACTOR;
+            }
+        }
+
+        let noise_deviation = match self.profile.snr_db {
+            Some(snr_db) => (self.signal_power / 10f32.powf(snr_db / 10.0)).sqrt(),
+            None => 0.0,
+        };
+
+        for sample in chunk.iter_mut() {
+            *sample += self.gaussian() * noise_deviation + self.profile.dc_offset;
+
+            if let Some(limit) = self.profile.clipping {
+                *sample = sample.clamp(-limit, limit);
+            }
+        }
+    }
+
+    fn resample(&mut self, sample: f32, output: &mut Vec<f32>) {
+        // Output sample `k` is taken at input time `k * drift_ratio`, linearly
+        // interpolated between the previous and the current input sample.
+        while self.drift_position <= self.drift_consumed {
+            let fraction = (self.drift_position - self.drift_consumed + 1.0) as f32;
+            output.push(self.last_sample * (1.0 - fraction) + sample * fraction);
+            self.drift_position += self.drift_ratio;
+        }
+
+        self.drift_consumed += 1.0;
+        self.last_sample = sample;
+    }
+
+    fn gaussian(&mut self) -> f32 {
+        // Box-Muller transform, the second value is thrown away for simplicity.
+        let u1 = self.rng.gen::<f32>().max(f32::MIN_POSITIVE);
+        let u2 = self.rng.gen::<f32>();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_SEQUENCE_SAMPLES: usize = 48000;
+
+    fn sine_wave() -> Vec<f32> {
+        (0..TEST_SEQUENCE_SAMPLES)
+            .map(|index| (index as f32 / 48000.0 * 2.0 * std::f32::consts::PI * 1000.0).sin())
+            .collect()
+    }
+
+    fn simulate(profile: ChannelProfile, input: &[f32]) -> Vec<f32> {
+        let mut simulator = ChannelSimulator::new(profile);
+        let mut output = Vec::new();
+        input
+            .chunks(1024)
+            .for_each(|chunk| simulator.process(chunk, &mut output));
+        output
+    }
+
+    #[test]
+    fn test_channel_ideal() {
+        let input = sine_wave();
+        assert_eq!(simulate(ChannelProfile::ideal(), &input), input);
+    }
+
+    #[test]
+    fn test_channel_reproducible() {
+        let input = sine_wave();
+        let first = simulate(ChannelProfile::air(), &input);
+        let second = simulate(ChannelProfile::air(), &input);
+        assert_eq!(first, second);
+
+        let reseeded = ChannelProfile {
+            seed: 1,
+            ..ChannelProfile::air()
+        };
+        assert_ne!(first, simulate(reseeded, &input));
+    }
+
+    #[test]
+    fn test_channel_delay_and_attenuation() {
+        let input = sine_wave();
+        let profile = ChannelProfile {
+            delay: 100,
+            attenuation_db: 20.0,
+            ..ChannelProfile::ideal()
+        };
+        let output = simulate(profile, &input);
+
+        assert!(output[..100].iter().all(|&x| x == 0.0));
+        output[100..]
+            .iter()
+            .zip(input.iter())
+            .for_each(|(a, b)| assert!((a - b / 10.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_channel_snr() {
+        let input = sine_wave();
+        let profile = ChannelProfile {
+            snr_db: Some(10.0),
+            ..ChannelProfile::ideal()
+        };
+        let output = simulate(profile, &input);
+
+        let noise_power = output
+            .iter()
+            .zip(input.iter())
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f32>()
+            / input.len() as f32;
+        let snr_db = 10.0 * (0.5 / noise_power).log10();
+
+        assert!((snr_db - 10.0).abs() < 0.5, "Measured SNR: {}", snr_db);
+    }
+
+    #[test]
+    fn test_channel_drift_and_clipping() {
+        let input = sine_wave();
+        let profile = ChannelProfile {
+            drift_ppm: -1000.0,
+            dc_offset: 0.5,
+            clipping: Some(1.0),
+            ..ChannelProfile::ideal()
+        };
+        let output = simulate(profile, &input);
+
+        assert_eq!(output.len(), TEST_SEQUENCE_SAMPLES + 48);
+        assert!(output.iter().all(|&x| (-1.0..=1.0).contains(&x)));
+        assert!(output.contains(&1.0));
+    }
+}
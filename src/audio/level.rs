@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::mixer::LIMITER_THRESHOLD;
+
+const CLIP_LEVEL: f32 = 0.999;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputLevel {
+    pub gain: f32,
+    pub ceiling: f32,
+}
+
+impl Default for OutputLevel {
+    fn default() -> Self {
+        // Peaks stay below where the mixer's soft limiter starts bending
+        // them, which also keeps the resampler overshoot clear of full scale.
+        Self {
+            gain: 1.0,
+            ceiling: LIMITER_THRESHOLD,
+        }
+    }
+}
+
+impl OutputLevel {
+    pub fn new(gain: f32) -> Self {
+        Self {
+            gain,
+            ..Self::default()
+        }
+    }
+
+    pub fn apply(&self, block: &mut [f32]) -> f32 {
+        // The whole block shares one factor, so amplitude and phase ratios
+        // within a packet survive and only its overall level drops.
+        let peak = block
+            .iter()
+            .fold(0.0, |peak: f32, sample| peak.max(sample.abs()));
+        let gain = if peak * self.gain > self.ceiling {
+            self.ceiling / peak
+        } else {
+            self.gain
+        };
+
+        block.iter_mut().for_each(|sample| *sample *= gain);
+        gain
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ClipCounter(Arc<AtomicUsize>);
+
+impl ClipCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&self, samples: &[f32]) {
+        let clipped = samples
+            .iter()
+            .filter(|sample| sample.abs() >= CLIP_LEVEL)
+            .count();
+
+        if clipped > 0 {
+            self.0.fetch_add(clipped, Ordering::Relaxed);
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_level() {
+        let samples = [0.5, -2.0, 1.0, 0.25];
+
+        let mut block = samples;
+        assert_eq!(OutputLevel::new(0.25).apply(&mut block), 0.25);
+        assert_eq!(block, [0.125, -0.5, 0.25, 0.0625]);
+
+        let mut block = samples;
+        let gain = OutputLevel::default().apply(&mut block);
+        assert_eq!(gain, 0.4);
+        assert!(block.iter().all(|sample| sample.abs() <= LIMITER_THRESHOLD));
+
+        let counter = ClipCounter::new();
+        counter.update(&samples);
+        counter.update(&block);
+        assert_eq!(counter.count(), 2);
+    }
+}
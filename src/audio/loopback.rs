@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::{AudioBackend, AudioCallback, AudioClock, AudioDeactivateFlag, AudioError};
+use super::{CallbackHandle, PlaybackMixer};
+use super::{ChannelProfile, ChannelSimulator};
+
+struct LoopbackShared {
+    buffer_size: usize,
+    timetick: AtomicUsize,
+    xruns: AtomicUsize,
+    running: AtomicBool,
+    mixer: Mutex<PlaybackMixer>,
+    channel: Mutex<ChannelSimulator>,
+}
+
+pub struct LoopbackAudio {
+    sample_rate: usize,
+    shared: Arc<LoopbackShared>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LoopbackAudio {
+    pub fn new(sample_rate: usize, buffer_size: usize) -> Arc<LoopbackAudio> {
+        Self::with_channel(sample_rate, buffer_size, ChannelProfile::ideal())
+    }
+
+    pub fn with_channel(
+        sample_rate: usize,
+        buffer_size: usize,
+        profile: ChannelProfile,
+    ) -> Arc<LoopbackAudio> {
+        let shared = LoopbackShared {
+            buffer_size,
+            timetick: AtomicUsize::new(0),
+            xruns: AtomicUsize::new(0),
+            running: AtomicBool::new(false),
+            mixer: Mutex::new(PlaybackMixer::new()),
+            channel: Mutex::new(ChannelSimulator::new(profile)),
+        };
+
+        shared.mixer.lock().unwrap().set_buffer_size(buffer_size);
+
+        Arc::new(LoopbackAudio {
+            sample_rate,
+            shared: Arc::new(shared),
+            worker: Mutex::new(None),
+        })
+    }
+
+    fn stop_worker(&self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            worker.join().unwrap();
+        }
+    }
+}
+
+impl LoopbackShared {
+    fn process(&self, capture: &[f32], playback: &mut [f32]) {
+        let clock = AudioClock {
+            timetick: self.timetick.load(Ordering::Relaxed),
+            frame_time: None,
+        };
+
+        self.mixer.lock().unwrap().process(clock, capture, playback);
+        self.timetick.fetch_add(self.buffer_size, Ordering::Relaxed);
+    }
+}
+
+impl AudioBackend for LoopbackAudio {
+    fn register_with_gain(&self, callback: AudioCallback, gain: f32) -> CallbackHandle {
+        self.shared.mixer.lock().unwrap().add(callback, gain)
+    }
+
+    fn activate(&self) -> Result<(), AudioError> {
+        let shared = self.shared.clone();
+        shared.running.store(true, Ordering::Relaxed);
+
+        let cycle_duration =
+            Duration::from_secs_f64(shared.buffer_size as f64 / self.sample_rate as f64);
+
+        let worker = std::thread::spawn(move || {
+            let mut received = Vec::new();
+            let mut capture = vec![0.0; shared.buffer_size];
+            let mut playback = vec![0.0; shared.buffer_size];
+            let mut deadline = Instant::now();
+
+            while shared.running.load(Ordering::Relaxed) {
+                let available = received.len().min(shared.buffer_size);
+                capture.fill(0.0);
+                capture[..available].copy_from_slice(&received[..available]);
+                received.drain(..available);
+
+                shared.process(&capture, &mut playback);
+                shared
+                    .channel
+                    .lock()
+                    .unwrap()
+                    .process(&playback, &mut received);
+
+                // A cycle that finishes after its deadline is what JACK would
+                // report as an xrun, so count it and resynchronise the clock.
+                deadline += cycle_duration;
+                let now = Instant::now();
+                if now > deadline {
+                    shared.xruns.fetch_add(1, Ordering::Relaxed);
+                    deadline = now;
+                } else {
+                    std::thread::sleep(deadline - now);
+                }
+            }
+        });
+
+        *self.worker.lock().unwrap() = Some(worker);
+        Ok(())
+    }
+
+    fn deactivate(&self, flag: AudioDeactivateFlag) {
+        self.stop_worker();
+
+        match flag {
+            AudioDeactivateFlag::Restart => {
+                self.shared.timetick.store(0, Ordering::Relaxed);
+                self.shared.xruns.store(0, Ordering::Relaxed);
+            }
+            AudioDeactivateFlag::CleanRestart => {
+                self.shared.mixer.lock().unwrap().clear();
+                self.shared.timetick.store(0, Ordering::Relaxed);
+                self.shared.xruns.store(0, Ordering::Relaxed);
+            }
+            AudioDeactivateFlag::Deactivate => {}
+        }
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn timetick(&self) -> usize {
+        self.shared.timetick.load(Ordering::Relaxed)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.shared.buffer_size
+    }
+
+    fn xruns(&self) -> usize {
+        self.shared.xruns.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for LoopbackAudio {
+    fn drop(&mut self) {
+        self.stop_worker();
+        self.shared.mixer.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,315 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use crossbeam_channel::{bounded, Receiver as ChannelReceiver, Sender as ChannelSender};
+
+use super::{AudioCallback, AudioClock, AudioPorts};
+
+pub(super) const LIMITER_THRESHOLD: f32 = 0.8;
+
+// Sources are handed in and out through fixed queues, so the mixing thread
+// never allocates or frees one.
+const MAX_SOURCES: usize = 64;
+
+struct SourceState {
+    paused: AtomicBool,
+    removed: AtomicBool,
+    gain: AtomicU32,
+}
+
+#[derive(Clone)]
+pub struct CallbackHandle(Arc<SourceState>);
+
+impl CallbackHandle {
+    pub fn pause(&self) {
+        self.0.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.0.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.0.gain.load(Ordering::Relaxed))
+    }
+
+    pub fn set_gain(&self, gain: f32) {
+        self.0.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn remove(&self) {
+        self.0.removed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.0.removed.load(Ordering::Relaxed)
+    }
+}
+
+struct MixerSource {
+    callback: AudioCallback,
+    state: Arc<SourceState>,
+}
+
+#[derive(Clone)]
+pub struct MixerInput {
+    pending: ChannelSender<MixerSource>,
+}
+
+impl MixerInput {
+    pub fn add(&self, callback: AudioCallback, gain: f32) -> CallbackHandle {
+        let state = Arc::new(SourceState {
+            paused: AtomicBool::new(false),
+            removed: AtomicBool::new(false),
+            gain: AtomicU32::new(gain.to_bits()),
+        });
+
+        let source = MixerSource {
+            callback,
+            state: state.clone(),
+        };
+        self.pending
+            .try_send(source)
+            .unwrap_or_else(|_| panic!("More than {} sources wait for the mixer!", MAX_SOURCES));
+
+        CallbackHandle(state)
+    }
+}
+
+pub struct PlaybackMixer {
+    sources: Vec<MixerSource>,
+    scratch: Vec<f32>,
+    input: MixerInput,
+    pending: ChannelReceiver<MixerSource>,
+    retired: ChannelSender<MixerSource>,
+}
+
+impl Default for PlaybackMixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlaybackMixer {
+    pub fn new() -> Self {
+        let (pending_sender, pending) = bounded(MAX_SOURCES);
+        let (retired, retired_receiver) = bounded::<MixerSource>(MAX_SOURCES);
+
+        // Removed callbacks are dropped here, away from the mixing thread.
+        std::thread::spawn(move || retired_receiver.iter().for_each(drop));
+
+        Self {
+            sources: Vec::with_capacity(MAX_SOURCES),
+            scratch: Vec::new(),
+            input: MixerInput {
+                pending: pending_sender,
+            },
+            pending,
+            retired,
+        }
+    }
+
+    pub fn set_buffer_size(&mut self, buffer_size: usize) {
+        self.scratch.resize(buffer_size, 0.0);
+    }
+
+    pub fn input(&self) -> MixerInput {
+        self.input.clone()
+    }
+
+    pub fn add(&self, callback: AudioCallback, gain: f32) -> CallbackHandle {
+        self.input.add(callback, gain)
+    }
+
+    pub fn clear(&mut self) {
+        self.sources.extend(self.pending.try_iter());
+        self.sources
+            .iter()
+            .for_each(|source| source.state.removed.store(true, Ordering::Relaxed));
+        self.sources.clear();
+    }
+
+    pub fn process(&mut self, clock: AudioClock, capture: &[f32], playback: &mut [f32]) {
+        playback.fill(0.0);
+
+        let free = self.sources.capacity() - self.sources.len();
+        self.sources.extend(self.pending.try_iter().take(free));
+        self.retire_removed();
+
+        // The scratch buffer is sized outside the cycle, until then the
+        // output stays silent.
+        let scratch = match self.scratch.get_mut(..playback.len()) {
+            Some(scratch) => scratch,
+            None => return,
+        };
+
+        for source in self.sources.iter_mut() {
+            if source.state.paused.load(Ordering::Relaxed)
+                || source.state.removed.load(Ordering::Relaxed)
+            {
+                continue;
+            }
+
+            scratch.fill(0.0);
+
+            let mut audio_ports = AudioPorts {
+                clock,
+                capture,
+                playback: scratch,
+            };
+            (source.callback)(&mut audio_ports);
+
+            let gain = f32::from_bits(source.state.gain.load(Ordering::Relaxed));
+            playback
+                .iter_mut()
+                .zip(scratch.iter())
+                .for_each(|(output, sample)| *output += sample * gain);
+        }
+
+        playback
+            .iter_mut()
+            .for_each(|sample| *sample = Self::soft_limit(*sample));
+    }
+
+    fn retire_removed(&mut self) {
+        // A removed source waits in place while the retire queue is full.
+        let mut index = 0;
+        while index < self.sources.len() {
+            if self.sources[index].state.removed.load(Ordering::Relaxed) && !self.retired.is_full()
+            {
+                let source = self.sources.swap_remove(index);
+                let _ = self.retired.try_send(source);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    fn soft_limit(sample: f32) -> f32 {
+        // Samples below the threshold pass untouched, the excess is squashed by
+        // tanh so that the output approaches but never reaches full scale.
+        let magnitude = sample.abs();
+        if magnitude <= LIMITER_THRESHOLD {
+            return sample;
+        }
+
+        let headroom = 1.0 - LIMITER_THRESHOLD;
+        let limited =
+            LIMITER_THRESHOLD + headroom * ((magnitude - LIMITER_THRESHOLD) / headroom).tanh();
+        limited.copysign(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const BUFFER_SIZE: usize = 256;
+
+    fn constant(value: f32) -> AudioCallback {
+        Box::new(move |ports: &mut AudioPorts| ports.playback.fill(value))
+    }
+
+    #[test]
+    fn test_mixer_sum() {
+        let mut mixer = PlaybackMixer::new();
+        mixer.set_buffer_size(BUFFER_SIZE);
+        mixer.add(constant(0.2), 1.0);
+        mixer.add(constant(0.3), 0.5);
+        mixer.add(Box::new(|_: &mut AudioPorts| {}), 1.0);
+
+        let capture = [0.0; BUFFER_SIZE];
+        let mut playback = [1.0; BUFFER_SIZE];
+        mixer.process(AudioClock::default(), &capture, &mut playback);
+
+        assert!(playback.iter().all(|&x| (x - 0.35).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_mixer_handle() {
+        let mut mixer = PlaybackMixer::new();
+        mixer.set_buffer_size(BUFFER_SIZE);
+        let first = mixer.add(constant(0.2), 1.0);
+        let second = mixer.add(constant(0.3), 1.0);
+
+        let capture = [0.0; BUFFER_SIZE];
+        let mut playback = [0.0; BUFFER_SIZE];
+
+        second.pause();
+        mixer.process(AudioClock::default(), &capture, &mut playback);
+        assert!(playback.iter().all(|&x| (x - 0.2).abs() < 1e-6));
+
+        second.resume();
+        second.set_gain(2.0);
+        first.remove();
+        mixer.process(AudioClock::default(), &capture, &mut playback);
+        assert!(playback.iter().all(|&x| (x - 0.6).abs() < 1e-6));
+        assert_eq!(mixer.sources.len(), 1);
+
+        mixer.clear();
+        assert!(second.is_removed());
+    }
+
+    #[test]
+    fn test_mixer_retire() {
+        struct DropThread(std::sync::mpsc::Sender<std::thread::ThreadId>);
+
+        impl Drop for DropThread {
+            fn drop(&mut self) {
+                self.0.send(std::thread::current().id()).unwrap();
+            }
+        }
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let guard = DropThread(sender);
+
+        let mut mixer = PlaybackMixer::new();
+        let input = mixer.input();
+        let capture = [0.0; BUFFER_SIZE];
+        let mut playback = [1.0; BUFFER_SIZE];
+
+        // Nothing is mixed before the buffer size is known.
+        input.add(constant(0.2), 1.0);
+        mixer.process(AudioClock::default(), &capture, &mut playback);
+        assert!(playback.iter().all(|&x| x == 0.0));
+
+        mixer.set_buffer_size(BUFFER_SIZE);
+        let handle = input.add(
+            Box::new(move |ports: &mut AudioPorts| {
+                let _ = &guard;
+                ports.playback.fill(0.3)
+            }),
+            1.0,
+        );
+        mixer.process(AudioClock::default(), &capture, &mut playback);
+        assert!(playback.iter().all(|&x| (x - 0.5).abs() < 1e-6));
+
+        handle.remove();
+        mixer.process(AudioClock::default(), &capture, &mut playback);
+        assert!(playback.iter().all(|&x| (x - 0.2).abs() < 1e-6));
+
+        let dropped_on = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_ne!(dropped_on, std::thread::current().id());
+    }
+
+    #[test]
+    fn test_mixer_limiter() {
+        let mut mixer = PlaybackMixer::new();
+        mixer.set_buffer_size(BUFFER_SIZE);
+        mixer.add(constant(0.9), 1.0);
+        mixer.add(constant(-0.9), 2.0);
+
+        let capture = [0.0; BUFFER_SIZE];
+        let mut playback = [0.0; BUFFER_SIZE];
+        mixer.process(AudioClock::default(), &capture, &mut playback);
+
+        assert!(playback.iter().all(|&x| x < -LIMITER_THRESHOLD && x > -1.0));
+        assert_eq!(PlaybackMixer::soft_limit(0.5), 0.5);
+        assert!(PlaybackMixer::soft_limit(100.0) <= 1.0);
+    }
+}
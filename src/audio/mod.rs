@@ -1,6 +1,31 @@
+mod backend;
+pub use backend::{
+    AudioBackend, AudioCallback, AudioClock, AudioDeactivateFlag, AudioError, AudioPorts,
+};
+
+mod mixer;
+pub use mixer::{CallbackHandle, MixerInput, PlaybackMixer};
+
 mod audio;
-pub use audio::{Audio, AudioPorts, AudioDeactivateFlag};
+pub use audio::{Audio, AudioRouting};
+
+mod channel;
+pub use channel::{ChannelProfile, ChannelSimulator};
+
+mod level;
+pub use level::{ClipCounter, OutputLevel};
+
+mod resampler;
+pub use resampler::Resampler;
+mod ring;
+pub use ring::{sample_ring, RingConsumer, RingProducer, RingStats};
+mod loopback;
+pub use loopback::LoopbackAudio;
 
+mod pipe;
+pub use pipe::{PcmFormat, PipeAudio};
+mod socket;
+pub use socket::{SocketAudio, SocketHub};
 mod callbacks;
 pub use callbacks::CreateCallback;
 
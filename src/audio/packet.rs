@@ -1,14 +1,66 @@
 use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 pub enum AudioPacketVariant {
     Buffer(Vec<f32>),
-    Reader(WavReader<BufReader<File>>),
+    Reader(WavSource),
     Writer(WavWriter<BufWriter<File>>),
 }
 
+pub struct WavSource {
+    reader: WavReader<BufReader<File>>,
+    channel: usize,
+    position: usize,
+}
+
+impl WavSource {
+    fn read_frame(&mut self, index: usize) -> Option<f32> {
+        if index >= self.reader.duration() as usize {
+            return None;
+        }
+
+        if index != self.position {
+            self.reader.seek(index as u32).unwrap();
+        }
+        self.position = index + 1;
+
+        let channels = self.reader.spec().channels as usize;
+        let mut selected = None;
+        for channel in 0..channels {
+            let sample = self.next_sample()?;
+            if channel == self.channel {
+                selected = Some(sample);
+            }
+        }
+
+        selected
+    }
+
+    fn read_all(&mut self) -> Vec<f32> {
+        let duration = self.reader.duration() as usize;
+        (0..duration)
+            .map_while(|index| self.read_frame(index))
+            .collect()
+    }
+
+    fn next_sample(&mut self) -> Option<f32> {
+        let spec = self.reader.spec();
+        match spec.sample_format {
+            SampleFormat::Float => self.reader.samples::<f32>().next().map(Result::unwrap),
+            SampleFormat::Int => {
+                let amplitude = (1u32 << (spec.bits_per_sample - 1)) as f32;
+                self.reader
+                    .samples::<i32>()
+                    .next()
+                    .map(|sample| sample.unwrap() as f32 / amplitude)
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AudioPacket {
     inner: Arc<Mutex<AudioPacketVariant>>,
@@ -22,14 +74,32 @@ impl AudioPacket {
         }
     }
 
-    pub fn create_reader(file: &'static str) -> Self {
+    pub fn create_reader(file: impl AsRef<Path>) -> Self {
+        Self::create_reader_channel(file, 0)
+    }
+
+    pub fn create_reader_channel(file: impl AsRef<Path>, channel: usize) -> Self {
         let reader = WavReader::open(file).unwrap();
+
+        let channels = reader.spec().channels as usize;
+        assert!(
+            channel < channels,
+            "Channel {} out of range, file has {} channels!",
+            channel,
+            channels
+        );
+
+        let source = WavSource {
+            reader,
+            channel,
+            position: 0,
+        };
         Self {
-            inner: Arc::new(Mutex::new(AudioPacketVariant::Reader(reader))),
+            inner: Arc::new(Mutex::new(AudioPacketVariant::Reader(source))),
         }
     }
 
-    pub fn create_writer(file: &'static str, sample_rate: u32) -> Self {
+    pub fn create_writer(file: impl AsRef<Path>, sample_rate: u32) -> Self {
         let wav_spec = WavSpec {
             channels: 1,
             bits_per_sample: 32,
@@ -42,17 +112,20 @@ impl AudioPacket {
         }
     }
 
+    pub fn sample_rate(&self) -> Option<u32> {
+        let container = self.inner.lock().unwrap();
+        match &*container {
+            AudioPacketVariant::Buffer(_) => None,
+            AudioPacketVariant::Reader(source) => Some(source.reader.spec().sample_rate),
+            AudioPacketVariant::Writer(writer) => Some(writer.spec().sample_rate),
+        }
+    }
+
     pub fn read_sample(&self, index: usize) -> Option<f32> {
         let mut container = self.inner.lock().unwrap();
         match &mut *container {
             AudioPacketVariant::Buffer(buffer) => buffer.get(index).copied(),
-            AudioPacketVariant::Reader(reader) => match reader.samples::<i16>().nth(0) {
-                Some(sample) => {
-                    const AMPLITUDE: f32 = i16::MAX as f32;
-                    Some(sample.unwrap() as f32 / AMPLITUDE)
-                }
-                None => None,
-            },
+            AudioPacketVariant::Reader(source) => source.read_frame(index),
             AudioPacketVariant::Writer(_) => panic!("Cannot read from writer!"),
         }
     }
@@ -61,13 +134,7 @@ impl AudioPacket {
         let mut container = self.inner.lock().unwrap();
         match &mut *container {
             AudioPacketVariant::Buffer(buffer) => buffer.clone(),
-            AudioPacketVariant::Reader(reader) => reader
-                .samples::<i16>()
-                .map(|sample| {
-                    const AMPLITUDE: f32 = i16::MAX as f32;
-                    sample.unwrap() as f32 / AMPLITUDE
-                })
-                .collect(),
+            AudioPacketVariant::Reader(source) => source.read_all(),
             AudioPacketVariant::Writer(_) => panic!("Cannot read from writer!"),
         }
     }
@@ -92,3 +159,82 @@ impl AudioPacket {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use temp_dir::TempDir;
+
+    const SAMPLE_RATE: u32 = 48000;
+    const TEST_FRAMES: usize = 64;
+
+    fn test_value(index: usize, channel: usize) -> f32 {
+        ((index * 7 + channel * 13) % 32) as f32 / 32.0 - 0.5
+    }
+
+    fn write_int(path: &Path, bits_per_sample: u16, channels: u16) {
+        let spec = WavSpec {
+            channels,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample,
+            sample_format: SampleFormat::Int,
+        };
+        let amplitude = (1i64 << (bits_per_sample - 1)) as f32;
+
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        for index in 0..TEST_FRAMES {
+            for channel in 0..channels as usize {
+                let sample = (test_value(index, channel) * amplitude) as i32;
+                writer.write_sample(sample).unwrap();
+            }
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_packet_int_formats() {
+        let temp_dir = TempDir::new().unwrap();
+
+        for bits_per_sample in [8, 16, 24, 32] {
+            let path = temp_dir.child(format!("int{}.wav", bits_per_sample));
+            write_int(&path, bits_per_sample, 1);
+
+            let packet = AudioPacket::create_reader(&path);
+            let samples = packet.read_all();
+
+            assert_eq!(samples.len(), TEST_FRAMES);
+            samples.iter().enumerate().for_each(|(index, &sample)| {
+                assert!((sample - test_value(index, 0)).abs() < 1e-2);
+            });
+        }
+    }
+
+    #[test]
+    fn test_packet_channel_seek() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.child("stereo.wav");
+
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        for index in 0..TEST_FRAMES {
+            writer.write_sample(test_value(index, 0)).unwrap();
+            writer.write_sample(test_value(index, 1)).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let packet = AudioPacket::create_reader_channel(&path, 1);
+        for index in [5, 6, 40, 2, TEST_FRAMES - 1] {
+            assert_eq!(packet.read_sample(index), Some(test_value(index, 1)));
+        }
+        assert_eq!(packet.read_sample(TEST_FRAMES), None);
+
+        let samples = packet.read_all();
+        assert_eq!(samples.len(), TEST_FRAMES);
+        assert_eq!(samples[10], test_value(10, 1));
+    }
+}
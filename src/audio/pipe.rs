@@ -0,0 +1,283 @@
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, Receiver as ChannelReceiver, RecvTimeoutError};
+
+use super::{AudioBackend, AudioCallback, AudioClock, AudioDeactivateFlag, AudioError};
+use super::{CallbackHandle, PlaybackMixer};
+
+const INPUT_QUEUE_BLOCKS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmFormat {
+    S16Le,
+    F32Le,
+}
+
+impl PcmFormat {
+    pub fn sample_bytes(self) -> usize {
+        match self {
+            Self::S16Le => 2,
+            Self::F32Le => 4,
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8], output: &mut [f32]) {
+        let chunks = bytes.chunks_exact(self.sample_bytes());
+        output.iter_mut().zip(chunks).for_each(|(sample, chunk)| {
+            *sample = match self {
+                Self::S16Le => {
+                    i16::from_le_bytes(chunk.try_into().unwrap()) as f32 / -(i16::MIN as f32)
+                }
+                Self::F32Le => f32::from_le_bytes(chunk.try_into().unwrap()),
+            }
+        });
+    }
+
+    pub fn encode(self, samples: &[f32], output: &mut Vec<u8>) {
+        samples.iter().for_each(|&sample| match self {
+            Self::S16Le => {
+                let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+                output.extend_from_slice(&sample.to_le_bytes());
+            }
+            Self::F32Le => output.extend_from_slice(&sample.to_le_bytes()),
+        });
+    }
+}
+
+impl FromStr for PcmFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "s16le" => Ok(Self::S16Le),
+            "f32le" => Ok(Self::F32Le),
+            _ => Err(format!(
+                "Unknown PCM format \"{}\", use s16le or f32le",
+                format
+            )),
+        }
+    }
+}
+
+struct PipeShared {
+    format: PcmFormat,
+    buffer_size: usize,
+    timetick: AtomicUsize,
+    xruns: AtomicUsize,
+    running: AtomicBool,
+    mixer: Mutex<PlaybackMixer>,
+    input: ChannelReceiver<Vec<f32>>,
+    output: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+pub struct PipeAudio {
+    sample_rate: usize,
+    shared: Arc<PipeShared>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl PipeAudio {
+    pub fn new(
+        input: Box<dyn Read + Send>,
+        output: Box<dyn Write + Send>,
+        format: PcmFormat,
+        sample_rate: usize,
+        buffer_size: usize,
+    ) -> Arc<PipeAudio> {
+        let (block_sender, block_receiver) = bounded(INPUT_QUEUE_BLOCKS);
+
+        // A blocking read cannot be interrupted, so the input lives on its own
+        // thread which exits once the stream ends or the backend is dropped.
+        std::thread::spawn(move || {
+            let mut input = input;
+            let mut bytes = vec![0; buffer_size * format.sample_bytes()];
+
+            while input.read_exact(&mut bytes).is_ok() {
+                let mut block = vec![0.0; buffer_size];
+                format.decode(&bytes, &mut block);
+
+                if block_sender.send(block).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let shared = PipeShared {
+            format,
+            buffer_size,
+            timetick: AtomicUsize::new(0),
+            xruns: AtomicUsize::new(0),
+            running: AtomicBool::new(false),
+            mixer: Mutex::new(PlaybackMixer::new()),
+            input: block_receiver,
+            output: Mutex::new(Some(output)),
+        };
+
+        shared.mixer.lock().unwrap().set_buffer_size(buffer_size);
+
+        Arc::new(PipeAudio {
+            sample_rate,
+            shared: Arc::new(shared),
+            worker: Mutex::new(None),
+        })
+    }
+
+    pub fn stdio(format: PcmFormat, sample_rate: usize, buffer_size: usize) -> Arc<PipeAudio> {
+        let input = Box::new(std::io::stdin());
+        let output = Box::new(std::io::stdout());
+        Self::new(input, output, format, sample_rate, buffer_size)
+    }
+
+    fn stop_worker(&self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            worker.join().unwrap();
+        }
+    }
+}
+
+impl PipeShared {
+    fn process(&self, capture: &[f32], playback: &mut [f32], bytes: &mut Vec<u8>) {
+        let clock = AudioClock {
+            timetick: self.timetick.load(Ordering::Relaxed),
+            frame_time: None,
+        };
+
+        self.mixer.lock().unwrap().process(clock, capture, playback);
+        self.timetick.fetch_add(self.buffer_size, Ordering::Relaxed);
+
+        bytes.clear();
+        self.format.encode(playback, bytes);
+
+        let mut output = self.output.lock().unwrap();
+        if let Some(writer) = output.as_mut() {
+            if let Err(error) = writer.write_all(bytes).and_then(|_| writer.flush()) {
+                warn!("Playback output closed: {}", error);
+                output.take();
+            }
+        }
+    }
+}
+
+impl AudioBackend for PipeAudio {
+    fn register_with_gain(&self, callback: AudioCallback, gain: f32) -> CallbackHandle {
+        self.shared.mixer.lock().unwrap().add(callback, gain)
+    }
+
+    fn activate(&self) -> Result<(), AudioError> {
+        let shared = self.shared.clone();
+        shared.running.store(true, Ordering::Relaxed);
+
+        let cycle_duration =
+            Duration::from_secs_f64(shared.buffer_size as f64 / self.sample_rate as f64);
+
+        let worker = std::thread::spawn(move || {
+            let mut capture = vec![0.0; shared.buffer_size];
+            let mut playback = vec![0.0; shared.buffer_size];
+            let mut bytes = Vec::new();
+            let mut deadline = Instant::now();
+
+            while shared.running.load(Ordering::Relaxed) {
+                // The input stream is the clock while it flows, once it ends the
+                // worker keeps running on silence at the nominal rate.
+                match shared.input.recv_timeout(cycle_duration) {
+                    Ok(block) => {
+                        capture.copy_from_slice(&block);
+                        deadline = Instant::now();
+                    }
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        capture.fill(0.0);
+
+                        deadline += cycle_duration;
+                        let now = Instant::now();
+                        if now > deadline {
+                            shared.xruns.fetch_add(1, Ordering::Relaxed);
+                            deadline = now;
+                        } else {
+                            std::thread::sleep(deadline - now);
+                        }
+                    }
+                }
+
+                shared.process(&capture, &mut playback, &mut bytes);
+            }
+        });
+
+        *self.worker.lock().unwrap() = Some(worker);
+        Ok(())
+    }
+
+    fn deactivate(&self, flag: AudioDeactivateFlag) {
+        self.stop_worker();
+
+        match flag {
+            AudioDeactivateFlag::Restart => {
+                self.shared.timetick.store(0, Ordering::Relaxed);
+                self.shared.xruns.store(0, Ordering::Relaxed);
+            }
+            AudioDeactivateFlag::CleanRestart => {
+                self.shared.mixer.lock().unwrap().clear();
+                self.shared.timetick.store(0, Ordering::Relaxed);
+                self.shared.xruns.store(0, Ordering::Relaxed);
+            }
+            AudioDeactivateFlag::Deactivate => {}
+        }
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn timetick(&self) -> usize {
+        self.shared.timetick.load(Ordering::Relaxed)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.shared.buffer_size
+    }
+
+    fn xruns(&self) -> usize {
+        self.shared.xruns.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for PipeAudio {
+    fn drop(&mut self) {
+        self.stop_worker();
+        self.shared.mixer.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcm_format() {
+        let samples = [0.0, 0.5, -0.5, 1.0, -1.0];
+
+        for format in [PcmFormat::S16Le, PcmFormat::F32Le] {
+            let mut bytes = Vec::new();
+            format.encode(&samples, &mut bytes);
+            assert_eq!(bytes.len(), samples.len() * format.sample_bytes());
+
+            let mut decoded = [0.0; 5];
+            format.decode(&bytes, &mut decoded);
+            assert!(samples
+                .iter()
+                .zip(decoded.iter())
+                .all(|(a, b)| (a - b).abs() < 1e-4));
+        }
+
+        assert_eq!("s16le".parse(), Ok(PcmFormat::S16Le));
+        assert_eq!("f32le".parse(), Ok(PcmFormat::F32Le));
+        assert!("u8".parse::<PcmFormat>().is_err());
+    }
+}
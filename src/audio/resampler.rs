@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+const ZERO_CROSSINGS: usize = 16;
+const TABLE_PHASES: usize = 256;
+const CUTOFF_ROLLOFF: f64 = 0.92;
+
+pub struct Resampler {
+    step: f64,
+    cutoff: f64,
+    half_width: usize,
+    kernel: Vec<f32>,
+    history: VecDeque<f32>,
+    position: f64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: usize, output_rate: usize) -> Self {
+        let step = input_rate as f64 / output_rate as f64;
+        let cutoff = (output_rate as f64 / input_rate as f64).min(1.0) * CUTOFF_ROLLOFF;
+        let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+
+        // One side of the symmetric Blackman windowed sinc, sampled at
+        // TABLE_PHASES points per zero crossing and looked up by distance.
+        let kernel = (0..=ZERO_CROSSINGS * TABLE_PHASES + 1)
+            .map(|index| {
+                let x = index as f64 / TABLE_PHASES as f64;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                let ratio = (x / ZERO_CROSSINGS as f64).min(1.0);
+                let window = 0.42 + 0.5 * (PI * ratio).cos() + 0.08 * (2.0 * PI * ratio).cos();
+                (sinc * window) as f32
+            })
+            .collect();
+
+        // Leading silence lets the first outputs see a full window, output
+        // sample n then lines up exactly with input time n * step.
+        let history = std::iter::repeat_n(0.0, half_width).collect();
+
+        Self {
+            step,
+            cutoff,
+            half_width,
+            kernel,
+            history,
+            position: half_width as f64,
+        }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.step
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        self.history.extend(input);
+
+        while self.position + self.half_width as f64 <= (self.history.len() - 1) as f64 {
+            output.push(self.interpolate(self.position));
+            self.position += self.step;
+        }
+
+        let consumed = (self.position as usize).saturating_sub(self.half_width);
+        self.history.drain(..consumed);
+        self.position -= consumed as f64;
+    }
+
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        let padding = vec![0.0; self.half_width + 1];
+        self.process(&padding, output);
+    }
+
+    fn interpolate(&self, position: f64) -> f32 {
+        let center = position.floor() as usize;
+        let first = center + 1 - self.half_width;
+        let last = center + self.half_width;
+
+        let sum: f32 = (first..=last)
+            .map(|index| {
+                let distance = (index as f64 - position).abs() * self.cutoff;
+                self.history[index] * self.tap(distance)
+            })
+            .sum();
+
+        sum * self.cutoff as f32
+    }
+
+    fn tap(&self, distance: f64) -> f32 {
+        let phase = distance * TABLE_PHASES as f64;
+        let index = phase as usize;
+        if index + 1 >= self.kernel.len() {
+            return 0.0;
+        }
+
+        let fraction = (phase - index as f64) as f32;
+        self.kernel[index] * (1.0 - fraction) + self.kernel[index + 1] * fraction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_FREQUENCY: f32 = 1000.0;
+
+    fn sine(sample_rate: usize, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|index| {
+                let time = index as f32 / sample_rate as f32;
+                (2.0 * std::f32::consts::PI * TEST_FREQUENCY * time).sin() * 0.5
+            })
+            .collect()
+    }
+
+    fn check_conversion(input_rate: usize, output_rate: usize) {
+        let input = sine(input_rate, input_rate / 10);
+        let expected = sine(output_rate, output_rate / 10);
+
+        let mut resampler = Resampler::new(input_rate, output_rate);
+        let mut output = Vec::new();
+        input
+            .chunks(333)
+            .for_each(|chunk| resampler.process(chunk, &mut output));
+        resampler.flush(&mut output);
+
+        assert!(output.len().abs_diff(expected.len()) <= resampler.half_width);
+
+        // Skip the edges where the window sees the leading and trailing silence.
+        let margin = resampler.half_width * 2;
+        let max_error = output[margin..expected.len() - margin]
+            .iter()
+            .zip(expected[margin..].iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(max_error < 1e-3, "max error {}", max_error);
+    }
+
+    #[test]
+    fn test_resampler_up() {
+        check_conversion(44100, 48000);
+    }
+
+    #[test]
+    fn test_resampler_down() {
+        check_conversion(48000, 44100);
+    }
+
+    #[test]
+    fn test_resampler_identity() {
+        check_conversion(48000, 48000);
+    }
+}
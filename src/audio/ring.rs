@@ -0,0 +1,229 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const BLOCKING_POLL_INTERVAL: Duration = Duration::from_micros(500);
+
+struct RingShared {
+    slots: Box<[AtomicU32]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    writer_pending: AtomicBool,
+    overruns: AtomicUsize,
+    underruns: AtomicUsize,
+}
+
+impl RingShared {
+    fn available(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+}
+
+pub fn sample_ring(capacity: usize) -> (RingProducer, RingConsumer) {
+    let slots = (0..capacity).map(|_| AtomicU32::new(0)).collect();
+
+    let shared = Arc::new(RingShared {
+        slots,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        writer_pending: AtomicBool::new(false),
+        overruns: AtomicUsize::new(0),
+        underruns: AtomicUsize::new(0),
+    });
+
+    (RingProducer(shared.clone()), RingConsumer(shared))
+}
+
+#[derive(Clone)]
+pub struct RingStats(Arc<RingShared>);
+
+impl RingStats {
+    pub fn overruns(&self) -> usize {
+        self.0.overruns.load(Ordering::Relaxed)
+    }
+
+    pub fn underruns(&self) -> usize {
+        self.0.underruns.load(Ordering::Relaxed)
+    }
+
+    pub fn available(&self) -> usize {
+        self.0.available()
+    }
+}
+
+pub struct RingProducer(Arc<RingShared>);
+
+impl RingProducer {
+    pub fn write(&mut self, samples: &[f32]) -> usize {
+        let shared = &self.0;
+        let capacity = shared.slots.len();
+
+        let tail = shared.tail.load(Ordering::Relaxed);
+        let head = shared.head.load(Ordering::Acquire);
+        let free = capacity - tail.wrapping_sub(head);
+        let count = samples.len().min(free);
+
+        samples[..count]
+            .iter()
+            .enumerate()
+            .for_each(|(offset, sample)| {
+                let slot = &shared.slots[tail.wrapping_add(offset) % capacity];
+                slot.store(sample.to_bits(), Ordering::Relaxed);
+            });
+
+        shared
+            .tail
+            .store(tail.wrapping_add(count), Ordering::Release);
+        shared
+            .overruns
+            .fetch_add(samples.len() - count, Ordering::Relaxed);
+
+        count
+    }
+
+    pub fn write_blocking(&mut self, mut samples: &[f32]) {
+        while !samples.is_empty() {
+            let capacity = self.0.slots.len();
+            let free = capacity - self.0.available();
+            let count = samples.len().min(free);
+
+            // Readers only count a short read as an underrun while a blocking
+            // writer waits with samples queued, silence between writes and
+            // before the first samples land is normal.
+            if count == 0 {
+                self.0.writer_pending.store(true, Ordering::Release);
+                std::thread::sleep(BLOCKING_POLL_INTERVAL);
+                continue;
+            }
+
+            self.write(&samples[..count]);
+            samples = &samples[count..];
+        }
+
+        self.0.writer_pending.store(false, Ordering::Release);
+    }
+
+    pub fn stats(&self) -> RingStats {
+        RingStats(self.0.clone())
+    }
+}
+
+pub struct RingConsumer(Arc<RingShared>);
+
+impl RingConsumer {
+    pub fn read(&mut self, output: &mut [f32]) -> usize {
+        let shared = &self.0;
+        let capacity = shared.slots.len();
+
+        let head = shared.head.load(Ordering::Relaxed);
+        let tail = shared.tail.load(Ordering::Acquire);
+        let count = output.len().min(tail.wrapping_sub(head));
+
+        output[..count]
+            .iter_mut()
+            .enumerate()
+            .for_each(|(offset, sample)| {
+                let slot = &shared.slots[head.wrapping_add(offset) % capacity];
+                *sample = f32::from_bits(slot.load(Ordering::Relaxed));
+            });
+
+        shared
+            .head
+            .store(head.wrapping_add(count), Ordering::Release);
+
+        if count < output.len() && shared.writer_pending.load(Ordering::Acquire) {
+            shared
+                .underruns
+                .fetch_add(output.len() - count, Ordering::Relaxed);
+        }
+
+        count
+    }
+
+    pub fn read_blocking(&mut self, mut output: &mut [f32]) {
+        while !output.is_empty() {
+            let count = self.read_available(output);
+
+            if count == 0 {
+                std::thread::sleep(BLOCKING_POLL_INTERVAL);
+                continue;
+            }
+
+            output = &mut output[count..];
+        }
+    }
+
+    pub fn stats(&self) -> RingStats {
+        RingStats(self.0.clone())
+    }
+
+    fn read_available(&mut self, output: &mut [f32]) -> usize {
+        let available = self.0.available();
+        let count = output.len().min(available);
+        self.read(&mut output[..count])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_wraparound() {
+        let (mut producer, mut consumer) = sample_ring(8);
+        let mut output = [0.0; 5];
+
+        for round in 0..10 {
+            let input: Vec<_> = (0..5).map(|index| (round * 5 + index) as f32).collect();
+            assert_eq!(producer.write(&input), 5);
+            assert_eq!(consumer.read(&mut output), 5);
+            assert_eq!(&output[..], &input[..]);
+        }
+
+        let stats = producer.stats();
+        assert_eq!(stats.overruns(), 0);
+        assert_eq!(stats.underruns(), 0);
+    }
+
+    #[test]
+    fn test_ring_counters() {
+        let (mut producer, mut consumer) = sample_ring(4);
+        let stats = consumer.stats();
+
+        assert_eq!(producer.write(&[1.0; 6]), 4);
+        assert_eq!(stats.overruns(), 2);
+
+        let mut output = [0.0; 6];
+        assert_eq!(consumer.read(&mut output), 4);
+        assert_eq!(stats.underruns(), 0);
+
+        producer.0.writer_pending.store(true, Ordering::Relaxed);
+        assert_eq!(consumer.read(&mut output), 0);
+        assert_eq!(stats.underruns(), 6);
+    }
+
+    #[test]
+    fn test_ring_threads() {
+        const SAMPLES: usize = 100_000;
+
+        let (mut producer, mut consumer) = sample_ring(1024);
+
+        let writer = std::thread::spawn(move || {
+            let input: Vec<_> = (0..SAMPLES).map(|index| index as f32).collect();
+            input
+                .chunks(300)
+                .for_each(|chunk| producer.write_blocking(chunk));
+        });
+
+        let mut output = vec![0.0; SAMPLES];
+        consumer.read_blocking(&mut output);
+        writer.join().unwrap();
+
+        assert!(output
+            .iter()
+            .enumerate()
+            .all(|(index, &sample)| sample == index as f32));
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::{AudioBackend, AudioCallback, AudioClock, AudioDeactivateFlag, AudioError};
+use super::{CallbackHandle, PcmFormat, PlaybackMixer};
+
+const SOCKET_FORMAT: PcmFormat = PcmFormat::F32Le;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const ENDPOINT_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_DATAGRAM_BYTES: usize = 65507;
+const ENDPOINT_QUEUE_BLOCKS: usize = 8;
+
+struct HubEndpoint {
+    playback: VecDeque<Vec<f32>>,
+    last_seen: Instant,
+}
+
+pub struct SocketHub {
+    socket: UdpSocket,
+    sample_rate: usize,
+    buffer_size: usize,
+    running: AtomicBool,
+}
+
+impl SocketHub {
+    pub fn bind(
+        address: impl ToSocketAddrs,
+        sample_rate: usize,
+        buffer_size: usize,
+    ) -> io::Result<SocketHub> {
+        let socket = UdpSocket::bind(address)?;
+
+        Ok(SocketHub {
+            socket,
+            sample_rate,
+            buffer_size,
+            running: AtomicBool::new(true),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    pub fn run(&self) -> io::Result<()> {
+        let cycle_duration =
+            Duration::from_secs_f64(self.buffer_size as f64 / self.sample_rate as f64);
+
+        let mut endpoints: HashMap<SocketAddr, HubEndpoint> = HashMap::new();
+        let mut datagram = vec![0; MAX_DATAGRAM_BYTES];
+        let mut medium = vec![0.0; self.buffer_size];
+        let mut bytes = Vec::new();
+        let mut deadline = Instant::now() + cycle_duration;
+
+        while self.running.load(Ordering::Relaxed) {
+            // Collect playback blocks until the cycle ends, every endpoint then
+            // hears the sum of all of them, its own included, like a room does.
+            let now = Instant::now();
+            if now < deadline {
+                self.socket.set_read_timeout(Some(deadline - now))?;
+
+                match self.socket.recv_from(&mut datagram) {
+                    Ok((length, address)) => {
+                        let endpoint = endpoints.entry(address).or_insert_with(|| {
+                            info!("Endpoint {} joined the hub", address);
+                            HubEndpoint {
+                                playback: VecDeque::new(),
+                                last_seen: now,
+                            }
+                        });
+                        endpoint.last_seen = now;
+
+                        // Blocks that arrive early wait for their own cycle, a
+                        // scheduling hiccup delays them instead of dropping one.
+                        let samples = length / SOCKET_FORMAT.sample_bytes();
+                        if samples == 0 {
+                            continue;
+                        }
+                        if endpoint.playback.len() == ENDPOINT_QUEUE_BLOCKS {
+                            warn!("Endpoint {} is too far ahead, block dropped", address);
+                            endpoint.playback.pop_front();
+                        }
+
+                        let mut block = vec![0.0; self.buffer_size];
+                        let samples = samples.min(self.buffer_size);
+                        SOCKET_FORMAT.decode(&datagram[..length], &mut block[..samples]);
+                        endpoint.playback.push_back(block);
+                    }
+                    Err(error) if Self::is_transient(&error) => {}
+                    Err(error) => warn!("Hub receive failed: {}", error),
+                }
+                continue;
+            }
+
+            deadline += cycle_duration;
+            if now > deadline {
+                deadline = now + cycle_duration;
+            }
+
+            endpoints.retain(|address, endpoint| {
+                let alive = now.duration_since(endpoint.last_seen) < ENDPOINT_TIMEOUT;
+                if !alive {
+                    info!("Endpoint {} left the hub", address);
+                }
+                alive
+            });
+
+            medium.fill(0.0);
+            endpoints.values_mut().for_each(|endpoint| {
+                if let Some(block) = endpoint.playback.pop_front() {
+                    medium
+                        .iter_mut()
+                        .zip(block.iter())
+                        .for_each(|(output, sample)| *output += sample);
+                }
+            });
+
+            bytes.clear();
+            SOCKET_FORMAT.encode(&medium, &mut bytes);
+            for address in endpoints.keys() {
+                if let Err(error) = self.socket.send_to(&bytes, address) {
+                    warn!("Hub send to {} failed: {}", address, error);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_transient(error: &io::Error) -> bool {
+        // Linux reports an endpoint that went away through the next receive,
+        // it simply times out of the hub a moment later.
+        matches!(
+            error.kind(),
+            io::ErrorKind::WouldBlock
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+        )
+    }
+}
+
+struct SocketShared {
+    socket: UdpSocket,
+    buffer_size: AtomicUsize,
+    timetick: AtomicUsize,
+    xruns: AtomicUsize,
+    running: AtomicBool,
+    mixer: Mutex<PlaybackMixer>,
+}
+
+pub struct SocketAudio {
+    sample_rate: usize,
+    shared: Arc<SocketShared>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SocketAudio {
+    pub fn connect(hub: impl ToSocketAddrs, sample_rate: usize) -> io::Result<Arc<SocketAudio>> {
+        let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+        socket.connect(hub)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let shared = SocketShared {
+            socket,
+            buffer_size: AtomicUsize::new(0),
+            timetick: AtomicUsize::new(0),
+            xruns: AtomicUsize::new(0),
+            running: AtomicBool::new(false),
+            mixer: Mutex::new(PlaybackMixer::new()),
+        };
+
+        Ok(Arc::new(SocketAudio {
+            sample_rate,
+            shared: Arc::new(shared),
+            worker: Mutex::new(None),
+        }))
+    }
+
+    fn stop_worker(&self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            worker.join().unwrap();
+        }
+    }
+}
+
+impl SocketShared {
+    fn process(&self, capture: &[f32], playback: &mut Vec<f32>, bytes: &mut Vec<u8>) {
+        let mut mixer = self.mixer.lock().unwrap();
+
+        if self.buffer_size.swap(capture.len(), Ordering::Relaxed) != capture.len() {
+            mixer.set_buffer_size(capture.len());
+            playback.resize(capture.len(), 0.0);
+        }
+
+        let clock = AudioClock {
+            timetick: self.timetick.load(Ordering::Relaxed),
+            frame_time: None,
+        };
+
+        mixer.process(clock, capture, playback);
+        self.timetick.fetch_add(capture.len(), Ordering::Relaxed);
+
+        bytes.clear();
+        SOCKET_FORMAT.encode(playback, bytes);
+        if let Err(error) = self.socket.send(bytes) {
+            warn!("Socket send failed: {}", error);
+        }
+    }
+}
+
+impl AudioBackend for SocketAudio {
+    fn register_with_gain(&self, callback: AudioCallback, gain: f32) -> CallbackHandle {
+        self.shared.mixer.lock().unwrap().add(callback, gain)
+    }
+
+    fn activate(&self) -> Result<(), AudioError> {
+        let shared = self.shared.clone();
+        let sample_rate = self.sample_rate;
+        shared.running.store(true, Ordering::Relaxed);
+
+        // An empty datagram registers the endpoint, the hub then drives the
+        // cycle by sending one capture block per period.
+        shared.socket.send(&[]).map_err(AudioError::Io)?;
+
+        let worker = std::thread::spawn(move || {
+            let mut datagram = vec![0; MAX_DATAGRAM_BYTES];
+            let mut capture = Vec::new();
+            let mut playback = Vec::new();
+            let mut bytes = Vec::new();
+            let mut last_block: Option<Instant> = None;
+
+            while shared.running.load(Ordering::Relaxed) {
+                let length = match shared.socket.recv(&mut datagram) {
+                    Ok(length) => length,
+                    Err(error) => {
+                        // Keep knocking until the hub is up and has registered us.
+                        if error.kind() == io::ErrorKind::ConnectionRefused {
+                            std::thread::sleep(POLL_INTERVAL);
+                        }
+                        shared.socket.send(&[]).ok();
+                        continue;
+                    }
+                };
+
+                capture.resize(length / SOCKET_FORMAT.sample_bytes(), 0.0);
+                SOCKET_FORMAT.decode(&datagram[..length], &mut capture);
+
+                // The hub sends a block every cycle, a gap of more than one
+                // means a block came late or was lost on the way.
+                let now = Instant::now();
+                let period = Duration::from_secs_f64(capture.len() as f64 / sample_rate as f64);
+                if last_block.is_some_and(|last| now.duration_since(last) > period * 2) {
+                    shared.xruns.fetch_add(1, Ordering::Relaxed);
+                }
+                last_block = Some(now);
+
+                shared.process(&capture, &mut playback, &mut bytes);
+            }
+        });
+
+        *self.worker.lock().unwrap() = Some(worker);
+        Ok(())
+    }
+
+    fn deactivate(&self, flag: AudioDeactivateFlag) {
+        self.stop_worker();
+
+        match flag {
+            AudioDeactivateFlag::Restart => {
+                self.shared.timetick.store(0, Ordering::Relaxed);
+                self.shared.xruns.store(0, Ordering::Relaxed);
+            }
+            AudioDeactivateFlag::CleanRestart => {
+                self.shared.mixer.lock().unwrap().clear();
+                self.shared.timetick.store(0, Ordering::Relaxed);
+                self.shared.xruns.store(0, Ordering::Relaxed);
+            }
+            AudioDeactivateFlag::Deactivate => {}
+        }
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn timetick(&self) -> usize {
+        self.shared.timetick.load(Ordering::Relaxed)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.shared.buffer_size.load(Ordering::Relaxed)
+    }
+
+    fn xruns(&self) -> usize {
+        self.shared.xruns.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for SocketAudio {
+    fn drop(&mut self) {
+        self.stop_worker();
+        self.shared.mixer.lock().unwrap().clear();
+    }
+}
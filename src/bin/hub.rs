@@ -0,0 +1,40 @@
+use argh::FromArgs;
+
+use audio_network::audio::SocketHub;
+
+#[macro_use]
+extern crate nolog;
+
+const DEFAULT_HUB_ADDRESS: &str = "127.0.0.1:41000";
+const DEFAULT_SAMPLE_RATE: usize = 48000;
+const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+#[derive(FromArgs)]
+#[argh(description = "Mix every connected socket endpoint like a shared acoustic medium")]
+struct Args {
+    #[argh(option, short = 'a')]
+    #[argh(description = "address the hub listens on")]
+    #[argh(default = "DEFAULT_HUB_ADDRESS.to_string()")]
+    address: String,
+
+    #[argh(option)]
+    #[argh(description = "sample rate of the medium")]
+    #[argh(default = "DEFAULT_SAMPLE_RATE")]
+    rate: usize,
+
+    #[argh(option)]
+    #[argh(description = "samples per cycle sent to each endpoint")]
+    #[argh(default = "DEFAULT_BUFFER_SIZE")]
+    buffer_size: usize,
+}
+
+fn main() {
+    let args: Args = argh::from_env();
+
+    let hub = SocketHub::bind(&args.address, args.rate, args.buffer_size).unwrap();
+    info!("Hub listening on {}", hub.local_addr().unwrap());
+
+    if let Err(error) = hub.run() {
+        error!("Hub stopped: {}", error);
+    }
+}
@@ -1,13 +1,31 @@
+use argh::FromArgs;
 use std::sync::Arc;
 
-use proj2_multiple_access::terminal::{Terminal, VALID_PACKET_BYTES};
+use audio_network::audio::SocketAudio;
+use proj2_multiple_access::terminal::Terminal;
 
 #[macro_use]
 extern crate nolog;
 
 const TEST_SEQUENCE_BYTES: usize = 6250;
+const DEFAULT_HUB_SAMPLE_RATE: usize = 48000;
+
+#[derive(FromArgs)]
+#[argh(description = "Send a test sequence between two CSMA terminals")]
+struct Args {
+    #[argh(option)]
+    #[argh(description = "address of a socket hub to use as a virtual cable instead of JACK")]
+    hub: Option<String>,
+
+    #[argh(option)]
+    #[argh(description = "sample rate of the socket hub medium")]
+    #[argh(default = "DEFAULT_HUB_SAMPLE_RATE")]
+    hub_rate: usize,
+}
 
 fn main() {
+    let args: Args = argh::from_env();
+
     let mac_slice1 = [0x00u8, 0x01];
     let mac_slice2 = [0x00u8, 0x02];
 
@@ -19,17 +37,28 @@ fn main() {
 
     warn!("Test data: {:?}", test_data);
 
-    let terminal1 = Arc::new(Terminal::new(mac_slice1));
-    let terminal2 = Arc::new(Terminal::new(mac_slice2));
+    // Each terminal joins the hub as its own endpoint, so they hear each
+    // other and collide just as two JACK clients on a shared cable would.
+    let create_terminal = |mac_address| match &args.hub {
+        Some(hub) => Terminal::with_audio(
+            mac_address,
+            SocketAudio::connect(hub, args.hub_rate).unwrap(),
+        ),
+        None => Terminal::new(mac_address),
+    };
+
+    let terminal1 = Arc::new(create_terminal(mac_slice1));
+    let terminal2 = Arc::new(create_terminal(mac_slice2));
 
     terminal1.activate();
     terminal2.activate();
+    let valid_packet_bytes = terminal1.valid_packet_bytes();
 
     let test_data_clone = test_data.clone();
     let terminal1_clone = terminal1.clone();
     let thread1 = std::thread::spawn(move || {
         test_data_clone
-            .chunks(VALID_PACKET_BYTES)
+            .chunks(valid_packet_bytes)
             .for_each(|chunk| {
                 warn!("[0] Send chunk: {:?}", chunk);
                 terminal1_clone.send(&chunk, &mac_slice2);
@@ -38,7 +67,7 @@ fn main() {
 
     let terminal2_clone = terminal2.clone();
     let thread2 = std::thread::spawn(move || {
-        let frame_count = TEST_SEQUENCE_BYTES.div_ceil(VALID_PACKET_BYTES);
+        let frame_count = TEST_SEQUENCE_BYTES.div_ceil(valid_packet_bytes);
 
         let mut result = (0..frame_count)
             .map(|_| terminal2_clone.recv())
@@ -1,20 +1,20 @@
-use crossbeam_channel::{after, select, tick, bounded};
+use crossbeam_channel::{after, bounded, select, tick};
 use crossbeam_channel::{Receiver as ChannelReceiver, Sender as ChannelSender};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use crate::corrupted::{CrcWrapper, CRC_BYTES};
-use audio_network::audio::Audio;
-use audio_network::modem::{Modem, Ofdm};
+use audio_network::audio::{Audio, AudioBackend};
+use audio_network::modem::{LinkProfile, Modem, Ofdm};
 use audio_network::node::{Receiver, Sender};
+use audio_network::packet::DetectorConfig;
 
 const ACK_MAGIC_NUMBER: [u8; 6] = [0x11, 0x45, 0x14, 0x19, 0x19, 0x81];
 const ACK_PAYLOAD_BYTES: usize = ACK_MAGIC_NUMBER.len();
 const MAC_ADDRESS_BYTES: usize = 2;
 const SEQUENCE_BYTES: usize = std::mem::size_of::<u32>();
-const DATA_FRAME_BYTES: usize = Ofdm::PREFERED_PAYLOAD_BYTES - CRC_BYTES;
-pub const VALID_PACKET_BYTES: usize = DATA_FRAME_BYTES - MAC_ADDRESS_BYTES * 2 - SEQUENCE_BYTES;
+const HEADER_BYTES: usize = MAC_ADDRESS_BYTES * 2 + SEQUENCE_BYTES;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MacAddress([u8; MAC_ADDRESS_BYTES]);
@@ -48,7 +48,6 @@ pub struct TerminalDataFrame {
 impl TerminalDataFrame {
     pub fn new(source: MacAddress, destination: MacAddress, sequence: u32, payload: &[u8]) -> Self {
         let payload = payload.to_vec();
-        assert_eq!(payload.len(), VALID_PACKET_BYTES);
 
         Self {
             source,
@@ -59,7 +58,7 @@ impl TerminalDataFrame {
     }
 
     pub fn from_bytes(data: &[u8]) -> Option<Self> {
-        if data.len() != DATA_FRAME_BYTES {
+        if data.len() < HEADER_BYTES {
             return None;
         }
 
@@ -78,7 +77,7 @@ impl TerminalDataFrame {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut result = Vec::with_capacity(DATA_FRAME_BYTES);
+        let mut result = Vec::with_capacity(HEADER_BYTES + self.payload.len());
         result.extend_from_slice(&self.source.0);
         result.extend_from_slice(&self.destination.0);
         result.extend_from_slice(&self.sequence.to_be_bytes());
@@ -90,8 +89,8 @@ impl TerminalDataFrame {
 struct AckPayload;
 
 impl AckPayload {
-    pub fn create() -> Vec<u8> {
-        let random_numbers: Vec<u8> = (0..VALID_PACKET_BYTES - ACK_MAGIC_NUMBER.len())
+    pub fn create(payload_bytes: usize) -> Vec<u8> {
+        let random_numbers: Vec<u8> = (0..payload_bytes - ACK_MAGIC_NUMBER.len())
             .map(|_| rand::random::<u8>())
             .collect();
 
@@ -99,10 +98,7 @@ impl AckPayload {
     }
 
     pub fn validate(data: &TerminalDataFrame) -> bool {
-        assert_eq!(data.payload.len(), VALID_PACKET_BYTES);
-
-        let (magic_number, _) = data.payload.split_at(ACK_PAYLOAD_BYTES);
-        return magic_number == ACK_MAGIC_NUMBER;
+        data.payload.get(..ACK_PAYLOAD_BYTES) == Some(&ACK_MAGIC_NUMBER[..])
     }
 }
 
@@ -113,6 +109,7 @@ enum SenderChannelData {
 
 pub struct Terminal {
     mac_address: MacAddress,
+    valid_packet_bytes: usize,
     running_state: Arc<AtomicBool>,
     sender_channel: TerminalChannelPair<SenderChannelData>,
     receiver_channel: TerminalChannelPair<TerminalDataFrame>,
@@ -125,26 +122,50 @@ pub struct Terminal {
 
 impl Terminal {
     pub fn new(mac_address: [u8; MAC_ADDRESS_BYTES]) -> Self {
-        let audio = Audio::new().unwrap();
-        audio.activate();
+        Self::with_audio(mac_address, Audio::new().unwrap())
+    }
+
+    pub fn with_audio(mac_address: [u8; MAC_ADDRESS_BYTES], audio: Arc<dyn AudioBackend>) -> Self {
+        audio.activate().unwrap();
+
+        let modem_rate = audio.sample_rate();
+        let detector_config = DetectorConfig::default();
+        let preamble_length = detector_config.preamble_length;
+
+        // Every frame fills one preferred payload, CRC and header included.
+        let modem = Ofdm::default();
+        let valid_packet_bytes = modem.prefered_payload_bytes() - CRC_BYTES - HEADER_BYTES;
 
         Self {
             mac_address: MacAddress::new(mac_address),
+            valid_packet_bytes,
             running_state: Arc::new(AtomicBool::new(true)),
             sender_channel: TerminalChannelPair::new(),
             receiver_channel: TerminalChannelPair::new(),
-            sender_node: Arc::new(Sender::<Ofdm>::new(&audio)),
-            receiver_node: Arc::new(Receiver::<Ofdm>::new(&audio)),
+            sender_node: Arc::new(
+                Sender::with_modem(audio.clone(), modem_rate, modem, preamble_length)
+                    .with_warmup(LinkProfile::default().warmup_bytes()),
+            ),
+            receiver_node: Arc::new(Receiver::with_modem(
+                audio.clone(),
+                modem_rate,
+                Ofdm::default(),
+                detector_config,
+            )),
             current_sequence: AtomicUsize::new(0),
             received_acks: Arc::new(Mutex::new(Vec::new())),
             received_sequences: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    pub fn valid_packet_bytes(&self) -> usize {
+        self.valid_packet_bytes
+    }
+
     pub fn send(&self, data: &[u8], destination: &[u8; MAC_ADDRESS_BYTES]) {
         let data = {
             let mut data = data.to_vec();
-            data.resize(VALID_PACKET_BYTES, 0);
+            data.resize(self.valid_packet_bytes, 0);
             data
         };
 
@@ -227,7 +248,7 @@ impl Terminal {
                                 {
                                     break;
                                 }
-                            
+
                                 /*loop {
                                     if average_power.colliding() {
                                         std::thread::sleep(Duration::from_millis(
@@ -259,6 +280,7 @@ impl Terminal {
 
     fn active_receiver(&self) {
         let mac_address = self.mac_address.clone();
+        let valid_packet_bytes = self.valid_packet_bytes;
         let receiver_node = self.receiver_node.clone();
         let running_state = self.running_state.clone();
         let sender_channel_sender = self.sender_channel.sender.clone();
@@ -280,7 +302,7 @@ impl Terminal {
                     if AckPayload::validate(&data_frame) {
                         received_acks.lock().unwrap().push(data_frame.sequence);
                     } else {
-                        let ack_payload = AckPayload::create();
+                        let ack_payload = AckPayload::create(valid_packet_bytes);
 
                         let ack_data_frame = TerminalDataFrame::new(
                             mac_address,
@@ -1,18 +1,24 @@
 use argh::FromArgs;
 use ipnet::Ipv4Net;
 use std::io::{Read, Write};
+use std::sync::Arc;
 
-use audio_network::audio::Audio;
-use audio_network::modem::Ofdm;
-use audio_network::node::{Receiver, Sender};
-
-type TargetModem = Ofdm;
+use audio_network::audio::{Audio, AudioBackend, AudioRouting, OutputLevel};
+use audio_network::audio::{PcmFormat, PipeAudio, SocketAudio};
+use audio_network::modem::{AnyModem, BitLoading, LinkProfile, ModemKind, ModemOptions, PskOrder};
+use audio_network::node::{ControlFrame, Receiver, Sender};
+use audio_network::packet::DetectorConfig;
 
 #[macro_use]
 extern crate nolog;
 
 const DEFAULT_INFERFACE_NAME: &str = "anp0";
 const DEFAULT_IP_ADDRESS: &str = "11.45.14.19/24";
+const DEFAULT_PIPE_SAMPLE_RATE: usize = 48000;
+const PIPE_BUFFER_SIZE: usize = 1024;
+const DEFAULT_HUB_SAMPLE_RATE: usize = 48000;
+const BIT_LOADING_ERROR_RATE: f32 = 1e-5;
+const BIT_LOADING_MARGIN_DB: f32 = 3.0;
 
 #[derive(FromArgs)]
 #[argh(description = "Create an audio based network interface")]
@@ -26,6 +32,67 @@ struct Args {
     #[argh(description = "the network IP network address")]
     #[argh(default = "DEFAULT_IP_ADDRESS.to_string()")]
     address: String,
+
+    #[argh(option)]
+    #[argh(description = "modem to run the link with, ofdm, psk, fsk or bitwave")]
+    #[argh(default = "ModemKind::default()")]
+    modem: ModemKind,
+
+    #[argh(option)]
+    #[argh(description = "link profile the modem and detector are tuned for, cable or air")]
+    #[argh(default = "LinkProfile::default()")]
+    profile: LinkProfile,
+
+    #[argh(option)]
+    #[argh(description = "PSK order overriding the profile, bpsk, qpsk or 8psk")]
+    psk_order: Option<PskOrder>,
+
+    #[argh(switch)]
+    #[argh(description = "encode PSK data in phase transitions, immune to polarity inversion")]
+    psk_differential: bool,
+
+    #[argh(switch)]
+    #[argh(description = "ask the peer to load OFDM subcarriers by the SNR measured here")]
+    bit_loading: bool,
+
+    #[argh(option)]
+    #[argh(description = "capture port name or pattern to connect from")]
+    capture: Option<String>,
+
+    #[argh(option)]
+    #[argh(description = "playback port name or pattern to connect to")]
+    playback: Option<String>,
+
+    #[argh(switch)]
+    #[argh(description = "leave the ports unconnected for external patching")]
+    no_connect: bool,
+
+    #[argh(option)]
+    #[argh(description = "sample rate the modem runs at, resampled to the device")]
+    modem_rate: Option<usize>,
+
+    #[argh(option)]
+    #[argh(description = "transmit gain, packets are still limited below full scale")]
+    #[argh(default = "1.0")]
+    tx_gain: f32,
+
+    #[argh(option)]
+    #[argh(description = "use raw s16le or f32le PCM on stdin/stdout instead of JACK")]
+    pipe: Option<PcmFormat>,
+
+    #[argh(option)]
+    #[argh(description = "sample rate of the raw PCM streams")]
+    #[argh(default = "DEFAULT_PIPE_SAMPLE_RATE")]
+    pipe_rate: usize,
+
+    #[argh(option)]
+    #[argh(description = "address of a socket hub to use as a virtual cable")]
+    hub: Option<String>,
+
+    #[argh(option)]
+    #[argh(description = "sample rate of the socket hub medium")]
+    #[argh(default = "DEFAULT_HUB_SAMPLE_RATE")]
+    hub_rate: usize,
 }
 
 fn main() {
@@ -47,13 +114,52 @@ fn main() {
         device.split()
     };
 
-    let audio = Audio::new().unwrap();
+    let routing = if args.no_connect {
+        AudioRouting::unconnected()
+    } else {
+        let default_routing = AudioRouting::default();
+        AudioRouting {
+            capture: args.capture.or(default_routing.capture),
+            playback: args.playback.or(default_routing.playback),
+        }
+    };
+
+    let audio: Arc<dyn AudioBackend> = match (args.pipe, args.hub) {
+        (Some(format), _) => PipeAudio::stdio(format, args.pipe_rate, PIPE_BUFFER_SIZE),
+        (None, Some(hub)) => SocketAudio::connect(hub, args.hub_rate).unwrap(),
+        (None, None) => Audio::with_routing(routing).unwrap(),
+    };
+
+    let modem_rate = args.modem_rate.unwrap_or(audio.sample_rate());
+    let detector_config = DetectorConfig::from(args.profile);
+    let modem_options = ModemOptions {
+        psk_order: args.psk_order,
+        psk_differential: args.psk_differential,
+    };
+    let create_modem = || AnyModem::with_kind(args.modem, modem_rate, args.profile, modem_options);
+
+    let (sender_modem, receiver_modem) = match (create_modem(), create_modem()) {
+        (Ok(sender_modem), Ok(receiver_modem)) => (sender_modem, receiver_modem),
+        (Err(error), _) | (_, Err(error)) => {
+            error!("Failed to create modem: {}", error);
+            return;
+        }
+    };
 
-    let frame_sander = Sender::<TargetModem>::new(&audio);
-    let frame_receiver = Receiver::<TargetModem>::new(&audio);
+    let preamble_length = detector_config.preamble_length;
+    let frame_sander = Sender::with_modem(audio.clone(), modem_rate, sender_modem, preamble_length)
+        .with_output_level(OutputLevel::new(args.tx_gain))
+        .with_warmup(args.profile.warmup_bytes());
+    let frame_sander = Arc::new(frame_sander);
+    let feedback_sander = frame_sander.clone();
+    let frame_receiver =
+        Receiver::with_modem(audio.clone(), modem_rate, receiver_modem, detector_config);
 
     info!("Activating audio client...");
-    audio.activate();
+    if let Err(error) = audio.activate() {
+        error!("Failed to activate audio client: {}", error);
+        return;
+    }
 
     std::thread::spawn(move || {
         let mut buf = vec![0u8; 4096];
@@ -67,14 +173,80 @@ fn main() {
         }
     });
 
+    let bit_loading = args.bit_loading;
     std::thread::spawn(move || loop {
         let frame_data = frame_receiver.recv();
-        if let Ok(_) = if_writer.write(&frame_data) {
-            info!("To interface: {:?}", frame_data);
+        match ControlFrame::from_bytes(&frame_data) {
+            Some(ControlFrame::BitLoadingRequest(bit_loading)) => {
+                apply_bit_loading(&feedback_sander, bit_loading);
+            }
+            Some(ControlFrame::BitLoadingApplied(bit_loading)) => {
+                match frame_receiver.set_bit_loading(bit_loading) {
+                    Ok(()) => info!(
+                        "Receiving with bit loading: {:?}",
+                        frame_receiver.bit_loading()
+                    ),
+                    Err(error) => warn!("Failed to follow bit loading: {}", error),
+                }
+            }
+            None => {
+                if let Ok(_) = if_writer.write(&frame_data) {
+                    info!("To interface: {:?}", frame_data);
+                }
+                if bit_loading {
+                    request_bit_loading(&frame_receiver, &feedback_sander);
+                }
+            }
         }
     });
 
+    if args.pipe.is_some() {
+        // Stdin carries the capture stream, so run until interrupted.
+        loop {
+            std::thread::park();
+        }
+    }
+
     info!("Press enter to destroy AcosticLink Network interface...");
     let mut input = String::new();
     std::io::stdin().read_line(&mut input).unwrap();
 }
+
+fn request_bit_loading(frame_receiver: &Receiver<AnyModem>, frame_sander: &Sender<AnyModem>) {
+    let current = match frame_receiver.bit_loading() {
+        Some(current) => current,
+        None => return,
+    };
+    let bit_loading = match frame_receiver.channel_snr() {
+        Some(snr) => BitLoading::allocate(&snr, BIT_LOADING_ERROR_RATE, BIT_LOADING_MARGIN_DB),
+        None => return,
+    };
+
+    // Asked again after every frame until the peer confirms, a lost request
+    // costs nothing but a retry. One this end could not read is never asked.
+    if bit_loading != current && frame_receiver.check_bit_loading(&bit_loading).is_ok() {
+        debug!("Requesting bit loading: {:?}", bit_loading);
+        frame_sander.send(&ControlFrame::BitLoadingRequest(bit_loading).to_bytes());
+    }
+}
+
+fn apply_bit_loading(frame_sander: &Sender<AnyModem>, bit_loading: BitLoading) {
+    if frame_sander.bit_loading().as_ref() == Some(&bit_loading) {
+        return;
+    }
+
+    // The peer decodes only the new allocation once it reads the
+    // confirmation, so nothing is confirmed that this end cannot send.
+    if let Err(error) = frame_sander.check_bit_loading(&bit_loading) {
+        warn!("Rejected bit loading: {}", error);
+        return;
+    }
+
+    // The confirmation still goes out with the old allocation, the peer
+    // switches right after reading it.
+    frame_sander.send(&ControlFrame::BitLoadingApplied(bit_loading.clone()).to_bytes());
+    match frame_sander.set_bit_loading(bit_loading) {
+        Ok(()) => info!("Sending with bit loading: {:?}", frame_sander.bit_loading()),
+        Err(error) => error!("Failed to apply checked bit loading: {}", error),
+    }
+}
@@ -0,0 +1,203 @@
+use super::{BitLoading, BitWave, Fsk, LinkProfile, Modem, Ofdm, Psk, PskConfig, PskOrder};
+use crate::number::FP;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModemKind {
+    #[default]
+    Ofdm,
+    Psk,
+    Fsk,
+    BitWave,
+}
+
+impl FromStr for ModemKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "ofdm" => Ok(Self::Ofdm),
+            "psk" => Ok(Self::Psk),
+            "fsk" => Ok(Self::Fsk),
+            "bitwave" => Ok(Self::BitWave),
+            _ => Err(format!(
+                "Unknown modem \"{}\", use ofdm, psk, fsk or bitwave",
+                kind
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModemOptions {
+    pub psk_order: Option<PskOrder>,
+    pub psk_differential: bool,
+}
+
+impl ModemOptions {
+    fn psk_config(&self, profile: LinkProfile) -> PskConfig {
+        let profile_config = PskConfig::from(profile);
+        PskConfig {
+            order: self.psk_order.unwrap_or(profile_config.order),
+            differential: self.psk_differential || profile_config.differential,
+            ..profile_config
+        }
+    }
+
+    fn has_psk_options(&self) -> bool {
+        self.psk_order.is_some() || self.psk_differential
+    }
+}
+
+pub enum AnyModem {
+    Ofdm(Ofdm),
+    Psk(Psk),
+    Fsk(Fsk),
+    BitWave(BitWave),
+}
+
+impl AnyModem {
+    pub fn with_kind(
+        kind: ModemKind,
+        sample_rate: usize,
+        profile: LinkProfile,
+        options: ModemOptions,
+    ) -> Result<Self, String> {
+        if kind != ModemKind::Psk && options.has_psk_options() {
+            return Err(format!("PSK options do not apply to the {:?} modem", kind));
+        }
+
+        Ok(match kind {
+            ModemKind::Ofdm => Self::Ofdm(Ofdm::with_config(profile.into())),
+            ModemKind::Psk => Self::Psk(Psk::with_config(sample_rate, options.psk_config(profile))),
+            ModemKind::Fsk => Self::Fsk(Fsk::with_config(sample_rate, profile.into())),
+            ModemKind::BitWave => Self::BitWave(BitWave::new(sample_rate)),
+        })
+    }
+
+    pub fn kind(&self) -> ModemKind {
+        match self {
+            Self::Ofdm(_) => ModemKind::Ofdm,
+            Self::Psk(_) => ModemKind::Psk,
+            Self::Fsk(_) => ModemKind::Fsk,
+            Self::BitWave(_) => ModemKind::BitWave,
+        }
+    }
+
+    fn inner(&self) -> &dyn Modem {
+        match self {
+            Self::Ofdm(modem) => modem,
+            Self::Psk(modem) => modem,
+            Self::Fsk(modem) => modem,
+            Self::BitWave(modem) => modem,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn Modem {
+        match self {
+            Self::Ofdm(modem) => modem,
+            Self::Psk(modem) => modem,
+            Self::Fsk(modem) => modem,
+            Self::BitWave(modem) => modem,
+        }
+    }
+}
+
+impl Modem for AnyModem {
+    fn new(sample_rate: usize) -> Self {
+        let (kind, options) = (ModemKind::default(), ModemOptions::default());
+        Self::with_kind(kind, sample_rate, LinkProfile::default(), options).unwrap()
+    }
+
+    fn min_modulate_bytes(&self) -> usize {
+        self.inner().min_modulate_bytes()
+    }
+
+    fn prefered_payload_bytes(&self) -> usize {
+        self.inner().prefered_payload_bytes()
+    }
+
+    fn preamble_frequency_range(&self) -> (f32, f32) {
+        self.inner().preamble_frequency_range()
+    }
+
+    fn modulate(&self, bytes: &[u8]) -> Vec<FP> {
+        self.inner().modulate(bytes)
+    }
+
+    fn demodulate(&self, samples: &[FP]) -> Vec<u8> {
+        self.inner().demodulate(samples)
+    }
+
+    fn demodulate_soft(&self, samples: &[FP]) -> Vec<f32> {
+        self.inner().demodulate_soft(samples)
+    }
+
+    fn channel_snr(&self, samples: &[FP]) -> Option<Vec<f32>> {
+        self.inner().channel_snr(samples)
+    }
+
+    fn bit_loading(&self) -> Option<BitLoading> {
+        self.inner().bit_loading()
+    }
+
+    fn check_bit_loading(&self, bit_loading: &BitLoading) -> Result<usize, String> {
+        self.inner().check_bit_loading(bit_loading)
+    }
+
+    fn set_bit_loading(&mut self, bit_loading: BitLoading) -> Result<(), String> {
+        self.inner_mut().set_bit_loading(bit_loading)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 48000;
+
+    #[test]
+    fn test_any_modem() {
+        for kind in [
+            ModemKind::Ofdm,
+            ModemKind::Psk,
+            ModemKind::Fsk,
+            ModemKind::BitWave,
+        ] {
+            let options = ModemOptions::default();
+            let modem =
+                AnyModem::with_kind(kind, SAMPLE_RATE, LinkProfile::Cable, options).unwrap();
+            assert_eq!(modem.kind(), kind);
+
+            let data = (0..modem.min_modulate_bytes() * 2)
+                .map(|index| index as u8)
+                .collect::<Vec<_>>();
+
+            let modulated = modem.modulate(&data);
+            assert_eq!(modem.demodulate(&modulated), data);
+        }
+
+        let options = ModemOptions {
+            psk_order: Some(PskOrder::Psk8),
+            psk_differential: false,
+        };
+        let modem = AnyModem::with_kind(ModemKind::Psk, SAMPLE_RATE, LinkProfile::Cable, options);
+        assert!(modem.is_ok_and(|modem| modem.min_modulate_bytes() > 0));
+        let modem = AnyModem::with_kind(ModemKind::Fsk, SAMPLE_RATE, LinkProfile::Cable, options);
+        assert!(modem.is_err());
+
+        // Only OFDM takes a new bit loading.
+        let options = ModemOptions::default();
+        let mut modem =
+            AnyModem::with_kind(ModemKind::Ofdm, SAMPLE_RATE, LinkProfile::Cable, options).unwrap();
+        let bit_loading = modem.bit_loading().unwrap();
+        assert!(modem.set_bit_loading(bit_loading.clone()).is_ok());
+        let mut modem =
+            AnyModem::with_kind(ModemKind::Psk, SAMPLE_RATE, LinkProfile::Cable, options).unwrap();
+        assert_eq!(modem.bit_loading(), None);
+        assert!(modem.set_bit_loading(bit_loading).is_err());
+
+        assert_eq!("fsk".parse(), Ok(ModemKind::Fsk));
+        assert!("qam".parse::<ModemKind>().is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use rustfft::num_complex::Complex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Constellation {
+    #[default]
+    Bpsk,
+    Qpsk,
+    Qam16,
+    Qam64,
+}
+
+impl Constellation {
+    pub fn bit_per_point(&self) -> usize {
+        match self {
+            Self::Bpsk => 1,
+            Self::Qpsk => 2,
+            Self::Qam16 => 4,
+            Self::Qam64 => 6,
+        }
+    }
+
+    fn axis_bits(&self) -> usize {
+        match self {
+            Self::Bpsk => 1,
+            _ => self.bit_per_point() / 2,
+        }
+    }
+
+    fn scale(&self) -> f32 {
+        // Every order is normalized to unit average energy, so switching the
+        // constellation leaves the transmit level where it was.
+        let levels = (1 << self.axis_bits()) as f32;
+        let axis_energy = (levels * levels - 1.0) / 3.0;
+
+        match self {
+            Self::Bpsk => axis_energy.sqrt().recip(),
+            _ => (2.0 * axis_energy).sqrt().recip(),
+        }
+    }
+
+    pub fn map(&self, bits: &[u8]) -> Complex<f32> {
+        let (real, imag) = bits.split_at(self.axis_bits());
+        let imag = match self {
+            Self::Bpsk => 0.0,
+            _ => Self::gray_level(imag),
+        };
+
+        Complex::new(Self::gray_level(real), imag) * self.scale()
+    }
+
+    pub fn demap(&self, point: Complex<f32>) -> Vec<u8> {
+        let point = point / self.scale();
+        let mut bits = Self::gray_bits(point.re, self.axis_bits());
+
+        if *self != Self::Bpsk {
+            bits.extend(Self::gray_bits(point.im, self.axis_bits()));
+        }
+        bits
+    }
+
+    pub fn soft_demap(&self, point: Complex<f32>, snr: f32) -> Vec<f32> {
+        // Max-log LLRs, the squared distance to the closest point with a one
+        // minus the one to the closest point with a zero, over the noise power.
+        let count = self.bit_per_point();
+        let mut nearest = vec![[f32::INFINITY; 2]; count];
+
+        for value in 0..1 << count {
+            let bits = (0..count)
+                .map(|index| ((value >> index) & 1) as u8)
+                .collect::<Vec<_>>();
+            let distance = (point - self.map(&bits)).norm_sqr();
+
+            nearest.iter_mut().zip(bits).for_each(|(nearest, bit)| {
+                nearest[bit as usize] = nearest[bit as usize].min(distance)
+            });
+        }
+
+        nearest
+            .iter()
+            .map(|[zero, one]| (one - zero) * snr)
+            .collect()
+    }
+
+    fn gray_level(bits: &[u8]) -> f32 {
+        // Adjacent levels differ in a single bit, so the usual one-level
+        // slip costs one bit instead of several.
+        let gray = bits
+            .iter()
+            .fold(0usize, |gray, bit| (gray << 1) | *bit as usize);
+        let index = (0..bits.len()).fold(gray, |index, shift| index ^ (gray >> (shift + 1)));
+
+        ((1 << bits.len()) - 1) as f32 - 2.0 * index as f32
+    }
+
+    fn gray_bits(level: f32, count: usize) -> Vec<u8> {
+        let max_index = (1 << count) - 1;
+        let index = ((max_index as f32 - level) / 2.0).round();
+        let index = index.clamp(0.0, max_index as f32) as usize;
+        let gray = index ^ (index >> 1);
+
+        (0..count)
+            .rev()
+            .map(|shift| ((gray >> shift) & 1) as u8)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constellation() {
+        for constellation in [
+            Constellation::Bpsk,
+            Constellation::Qpsk,
+            Constellation::Qam16,
+            Constellation::Qam64,
+        ] {
+            let count = constellation.bit_per_point();
+            let points = (0..1 << count)
+                .map(|value| {
+                    let bits = (0..count)
+                        .map(|shift| ((value >> shift) & 1) as u8)
+                        .collect::<Vec<_>>();
+                    let point = constellation.map(&bits);
+                    assert_eq!(constellation.demap(point * 0.9), bits);
+
+                    let llrs = constellation.soft_demap(point * 0.9, 10.0);
+                    let hard = llrs.iter().map(|llr| (*llr < 0.0) as u8);
+                    assert!(hard.eq(bits.iter().copied()));
+                    (bits, point)
+                })
+                .collect::<Vec<_>>();
+
+            let energy = points
+                .iter()
+                .map(|(_, point)| point.norm_sqr())
+                .sum::<f32>();
+            assert!((energy / points.len() as f32 - 1.0).abs() < 1e-4);
+
+            // Nearest neighbours differ in exactly one bit.
+            let distance = 2.0 * constellation.scale() + 1e-4;
+            for (bits, point) in points.iter() {
+                for (other_bits, other) in points.iter() {
+                    if point != other && (point - other).norm() < distance {
+                        let differ = bits.iter().zip(other_bits).filter(|(a, b)| a != b);
+                        assert_eq!(differ.count(), 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_soft_demap() {
+        // Halfway between the two BPSK points nothing is known, and the
+        // confidence grows with the SNR.
+        let bpsk = Constellation::Bpsk;
+        assert_eq!(bpsk.soft_demap(Complex::new(0.0, 0.0), 10.0), [0.0]);
+        assert_eq!(bpsk.soft_demap(Complex::new(0.5, 0.0), 10.0), [20.0]);
+        assert_eq!(bpsk.soft_demap(Complex::new(0.5, 0.0), 1.0), [2.0]);
+
+        // The outer 16-QAM bit is sure of the sign, the inner one is not.
+        let qam16 = Constellation::Qam16;
+        let llrs = qam16.soft_demap(Complex::new(2.0, 2.0) * qam16.scale(), 10.0);
+        assert!(llrs[0].abs() > llrs[1].abs());
+        assert!(llrs[1].abs() < 1e-4);
+    }
+}
@@ -0,0 +1,328 @@
+use super::{BitByteConverter, LinkProfile, Modem};
+use crate::number::FP;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FskConfig {
+    pub tone_count: usize,
+    pub base_frequency: f32,
+    pub tone_spacing: f32,
+    pub symbol_rate: usize,
+    pub guard_ratio: f32,
+    pub prefered_payload_bytes: usize,
+    pub preamble_frequency_range: (f32, f32),
+}
+
+impl FskConfig {
+    pub fn cable() -> Self {
+        Self {
+            tone_count: 16,
+            base_frequency: 1000.0,
+            tone_spacing: 250.0,
+            symbol_rate: 250,
+            guard_ratio: 0.0,
+            prefered_payload_bytes: 32,
+            preamble_frequency_range: (900.0, 3000.0),
+        }
+    }
+
+    pub fn air() -> Self {
+        // Long symbols with the head skipped, the echoes of the previous tone
+        // die out in the guard before the detector starts listening.
+        Self {
+            tone_count: 8,
+            tone_spacing: 250.0,
+            symbol_rate: 100,
+            guard_ratio: 0.2,
+            prefered_payload_bytes: 16,
+            ..Self::cable()
+        }
+    }
+
+    fn bit_per_symbol(&self) -> usize {
+        self.tone_count.ilog2() as usize
+    }
+
+    fn frequency(&self, tone: usize) -> f32 {
+        self.base_frequency + tone as f32 * self.tone_spacing
+    }
+
+    fn symbol_samples(&self, sample_rate: usize) -> usize {
+        self.guard_samples(sample_rate) + self.window_samples(sample_rate)
+    }
+
+    fn guard_samples(&self, sample_rate: usize) -> usize {
+        ((sample_rate / self.symbol_rate) as f32 * self.guard_ratio) as usize
+    }
+
+    fn window_samples(&self, sample_rate: usize) -> usize {
+        // The window is stretched to whole periods of the tone spacing, where
+        // the neighbouring tones cancel out, so the symbol rate is only a
+        // target when the sample rate does not divide evenly.
+        let period = sample_rate as f32 / self.tone_spacing;
+        let window = (sample_rate / self.symbol_rate - self.guard_samples(sample_rate)) as f32;
+        ((window / period).round().max(1.0) * period).ceil() as usize
+    }
+}
+
+impl From<LinkProfile> for FskConfig {
+    fn from(profile: LinkProfile) -> Self {
+        match profile {
+            LinkProfile::Cable => Self::cable(),
+            LinkProfile::Air => Self::air(),
+        }
+    }
+}
+
+impl Default for FskConfig {
+    fn default() -> Self {
+        LinkProfile::default().into()
+    }
+}
+
+pub struct Fsk {
+    config: FskConfig,
+    sample_rate: usize,
+    goertzel_coefficients: Vec<f32>,
+}
+
+impl Modem for Fsk {
+    fn new(sample_rate: usize) -> Self {
+        Self::with_config(sample_rate, FskConfig::default())
+    }
+
+    fn min_modulate_bytes(&self) -> usize {
+        self.config.prefered_payload_bytes
+    }
+
+    fn prefered_payload_bytes(&self) -> usize {
+        self.config.prefered_payload_bytes
+    }
+
+    fn preamble_frequency_range(&self) -> (f32, f32) {
+        self.config.preamble_frequency_range
+    }
+
+    fn modulate(&self, bytes: &[u8]) -> Vec<FP> {
+        let bit_per_symbol = self.config.bit_per_symbol();
+        let symbol_samples = self.config.symbol_samples(self.sample_rate);
+
+        let mut bits = BitByteConverter::bytes_to_bits(bytes);
+        bits.resize(bits.len().next_multiple_of(bit_per_symbol), 0);
+
+        // The phase carries on from one tone to the next, the jumps of a
+        // restarted sine would splatter energy into the neighbouring tones.
+        let mut phase = 0.0f32;
+        bits.chunks(bit_per_symbol)
+            .flat_map(|chunk| {
+                let value = chunk
+                    .iter()
+                    .rev()
+                    .fold(0, |value, bit| (value << 1) | *bit as usize);
+                let step = 2.0 * std::f32::consts::PI * self.config.frequency(Self::gray(value))
+                    / self.sample_rate as f32;
+
+                (0..symbol_samples)
+                    .map(|_| {
+                        let sample = FP::from(phase.sin());
+                        phase = (phase + step) % (2.0 * std::f32::consts::PI);
+                        sample
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn demodulate(&self, samples: &[FP]) -> Vec<u8> {
+        let mut bits = self
+            .tone_magnitudes(samples)
+            .iter()
+            .flat_map(|magnitudes| self.symbol_bits(Self::loudest(magnitudes)))
+            .collect::<Vec<_>>();
+
+        bits.truncate(bits.len() / 8 * 8);
+        BitByteConverter::bits_to_bytes(&bits)
+    }
+
+    fn demodulate_soft(&self, samples: &[FP]) -> Vec<f32> {
+        let symbols = self.tone_magnitudes(samples);
+
+        // The loudest tone of every symbol gives the signal level, the quiet
+        // ones left over give the noise every tone picks up alike.
+        let (amplitude, noise) = {
+            let count = symbols.len().max(1) as f32;
+            let amplitude = symbols
+                .iter()
+                .map(|magnitudes| magnitudes[Self::loudest(magnitudes)])
+                .sum::<f32>()
+                / count;
+            let noise = symbols
+                .iter()
+                .map(|magnitudes| {
+                    let loudest = Self::loudest(magnitudes);
+                    magnitudes
+                        .iter()
+                        .enumerate()
+                        .filter(|(tone, _)| *tone != loudest)
+                        .map(|(_, magnitude)| magnitude.powi(2))
+                        .sum::<f32>()
+                        / (magnitudes.len() - 1) as f32
+                })
+                .sum::<f32>()
+                / count;
+            (amplitude, noise.max(f32::MIN_POSITIVE))
+        };
+
+        let mut llrs = symbols
+            .iter()
+            .flat_map(|magnitudes| {
+                let mut loudest = vec![[0.0f32; 2]; self.config.bit_per_symbol()];
+                magnitudes.iter().enumerate().for_each(|(tone, magnitude)| {
+                    loudest
+                        .iter_mut()
+                        .zip(self.symbol_bits(tone))
+                        .for_each(|(loudest, bit)| {
+                            loudest[bit as usize] = loudest[bit as usize].max(*magnitude)
+                        });
+                });
+
+                loudest
+                    .into_iter()
+                    .map(|[zero, one]| (zero - one) * amplitude / noise)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        llrs.truncate(llrs.len() / 8 * 8);
+        llrs
+    }
+}
+
+impl Fsk {
+    pub fn with_config(sample_rate: usize, config: FskConfig) -> Self {
+        assert!(
+            config.tone_count.is_power_of_two() && config.tone_count >= 2,
+            "The tone count must be a power of two!"
+        );
+
+        let goertzel_coefficients = (0..config.tone_count)
+            .map(|tone| {
+                let omega =
+                    2.0 * std::f32::consts::PI * config.frequency(tone) / sample_rate as f32;
+                2.0 * omega.cos()
+            })
+            .collect();
+
+        Self {
+            config,
+            sample_rate,
+            goertzel_coefficients,
+        }
+    }
+
+    fn tone_magnitudes(&self, samples: &[FP]) -> Vec<Vec<f32>> {
+        let guard = self.config.guard_samples(self.sample_rate);
+
+        samples
+            .chunks_exact(self.config.symbol_samples(self.sample_rate))
+            .map(|chunk| {
+                let window = &chunk[guard..];
+                self.goertzel_coefficients
+                    .iter()
+                    .map(|coefficient| {
+                        let (s1, s2) = window.iter().fold((0.0, 0.0), |(s1, s2), sample| {
+                            (FP::into::<f32>(*sample) + coefficient * s1 - s2, s1)
+                        });
+                        let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+                        power.max(0.0).sqrt() / window.len() as f32
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn loudest(magnitudes: &[f32]) -> usize {
+        (0..magnitudes.len())
+            .max_by(|a, b| magnitudes[*a].partial_cmp(&magnitudes[*b]).unwrap())
+            .unwrap()
+    }
+
+    fn symbol_bits(&self, tone: usize) -> Vec<u8> {
+        let value = Self::gray_inverse(tone);
+        (0..self.config.bit_per_symbol())
+            .map(|shift| ((value >> shift) & 1) as u8)
+            .collect()
+    }
+
+    fn gray(value: usize) -> usize {
+        // A drifting tone lands in its neighbour, which then costs one bit.
+        value ^ (value >> 1)
+    }
+
+    fn gray_inverse(gray: usize) -> usize {
+        let mut value = gray;
+        let mut shift = gray >> 1;
+        while shift > 0 {
+            value ^= shift;
+            shift >>= 1;
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modem::tests::assert_erasure;
+
+    const SAMPLE_RATE: usize = 48000;
+
+    #[test]
+    fn test_fsk() {
+        for (sample_rate, config) in [
+            (SAMPLE_RATE, FskConfig::cable()),
+            (SAMPLE_RATE, FskConfig::air()),
+            (44100, FskConfig::cable()),
+            (44100, FskConfig::air()),
+        ] {
+            let fsk = Fsk::with_config(sample_rate, config.clone());
+            let data = (0..fsk.min_modulate_bytes() * 2)
+                .map(|_| rand::random::<u8>())
+                .collect::<Vec<_>>();
+
+            let mut modulated = fsk.modulate(&data);
+            let symbols = (data.len() * 8).div_ceil(config.bit_per_symbol());
+            assert_eq!(
+                modulated.len(),
+                symbols * config.symbol_samples(sample_rate)
+            );
+            let window = config.window_samples(sample_rate);
+            assert!(config.tone_spacing * window as f32 >= sample_rate as f32);
+
+            // Noise twice the tone amplitude, the detector averages it away
+            // over the symbol.
+            modulated.iter_mut().for_each(|sample| {
+                *sample += FP::from((rand::random::<f32>() - 0.5) * 4.0);
+            });
+
+            assert_eq!(fsk.demodulate(&modulated), data);
+            let llrs = fsk.demodulate_soft(&modulated);
+            assert_eq!(BitByteConverter::llrs_to_bytes(&llrs), data);
+        }
+
+        assert!((0..16).all(|value| Fsk::gray_inverse(Fsk::gray(value)) == value));
+    }
+
+    #[test]
+    fn test_fsk_soft() {
+        let fsk = Fsk::new(SAMPLE_RATE);
+        let data = (0..fsk.min_modulate_bytes())
+            .map(|_| rand::random::<u8>())
+            .collect::<Vec<_>>();
+
+        // A silenced symbol leaves every tone equally quiet, so its bits
+        // come out erased.
+        let symbol_samples = fsk.config.symbol_samples(SAMPLE_RATE);
+        let bits = fsk.config.bit_per_symbol();
+        assert_erasure(&fsk, fsk.modulate(&data), 0..symbol_samples, 0..bits);
+    }
+}
@@ -0,0 +1,104 @@
+use super::Constellation;
+
+const CONSTELLATIONS: [Constellation; 4] = [
+    Constellation::Bpsk,
+    Constellation::Qpsk,
+    Constellation::Qam16,
+    Constellation::Qam64,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitLoading(Vec<Option<Constellation>>);
+
+impl BitLoading {
+    pub fn uniform(sub_carrier_count: usize, constellation: Constellation) -> Self {
+        Self(vec![Some(constellation); sub_carrier_count])
+    }
+
+    pub fn allocate(snr_db: &[f32], target_error_rate: f32, margin_db: f32) -> Self {
+        // The SNR gap of uncoded square QAM at the target symbol error rate,
+        // a carrier then holds log2(1 + SNR / gap) bits.
+        let gap = -(target_error_rate / 4.0).ln() / 1.5;
+
+        let carriers = snr_db
+            .iter()
+            .map(|snr_db| {
+                let snr = 10f32.powf((snr_db - margin_db) / 10.0);
+                let capacity = (1.0 + snr / gap).log2();
+
+                CONSTELLATIONS
+                    .iter()
+                    .rev()
+                    .find(|constellation| constellation.bit_per_point() as f32 <= capacity)
+                    .copied()
+            })
+            .collect();
+
+        Self(carriers)
+    }
+
+    pub fn carriers(&self) -> &[Option<Constellation>] {
+        &self.0
+    }
+
+    pub fn bit_per_symbol(&self) -> usize {
+        self.0
+            .iter()
+            .flatten()
+            .map(|constellation| constellation.bit_per_point())
+            .sum()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0
+            .iter()
+            .map(|carrier| carrier.map_or(0, |constellation| constellation.bit_per_point() as u8))
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bytes
+            .iter()
+            .map(|&bits| match bits {
+                0 => Some(None),
+                _ => CONSTELLATIONS
+                    .iter()
+                    .find(|constellation| constellation.bit_per_point() == bits as usize)
+                    .map(|constellation| Some(*constellation)),
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bit_loading() {
+        let snr_db = [-5.0, 10.0, 15.0, 22.0, 28.0, 60.0];
+        let loading = BitLoading::allocate(&snr_db, 1e-5, 0.0);
+
+        assert_eq!(
+            loading.carriers(),
+            [
+                None,
+                Some(Constellation::Bpsk),
+                Some(Constellation::Qpsk),
+                Some(Constellation::Qam16),
+                Some(Constellation::Qam64),
+                Some(Constellation::Qam64),
+            ]
+        );
+        assert_eq!(loading.bit_per_symbol(), 19);
+
+        let bytes = loading.to_bytes();
+        assert_eq!(bytes, [0, 1, 2, 4, 6, 6]);
+        assert_eq!(BitLoading::from_bytes(&bytes), Some(loading));
+        assert_eq!(BitLoading::from_bytes(&[3]), None);
+
+        let cautious = BitLoading::allocate(&snr_db, 1e-5, 10.0);
+        assert!(cautious.bit_per_symbol() < 19);
+    }
+}
@@ -1,22 +1,100 @@
 use crate::number::FP;
+use std::str::FromStr;
 
 mod psk;
-pub use psk::Psk;
+pub use psk::{Psk, PskConfig, PskOrder};
+
+mod constellation;
+pub use constellation::Constellation;
+
+mod loading;
+pub use loading::BitLoading;
 
 mod ofdm;
-pub use ofdm::Ofdm;
+pub use ofdm::{Ofdm, OfdmConfig};
+
+mod fsk;
+pub use fsk::{Fsk, FskConfig};
 
 mod xbyb;
 pub use xbyb::BitWave;
 
-pub trait Modem {
-    const MIN_MODULATE_BYTES: usize;
-    const PREFERED_PAYLOAD_BYTES: usize;
-    const PREAMBLE_FREQUENCY_RANGE: (f32, f32);
+mod any;
+pub use any::{AnyModem, ModemKind, ModemOptions};
 
-    fn new(sample_rate: usize) -> Self;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkProfile {
+    Cable,
+    Air,
+}
+
+impl Default for LinkProfile {
+    fn default() -> Self {
+        if cfg!(feature = "cable_link") {
+            Self::Cable
+        } else {
+            Self::Air
+        }
+    }
+}
+
+impl LinkProfile {
+    pub fn warmup_bytes(&self) -> usize {
+        // Over the air the first frame is led by some random data, so the
+        // sound card and the receiver's AGC have settled by its preamble.
+        match self {
+            Self::Cable => 0,
+            Self::Air => 24,
+        }
+    }
+}
+
+impl FromStr for LinkProfile {
+    type Err = String;
+
+    fn from_str(profile: &str) -> Result<Self, Self::Err> {
+        match profile {
+            "cable" => Ok(Self::Cable),
+            "air" => Ok(Self::Air),
+            _ => Err(format!(
+                "Unknown link profile \"{}\", use cable or air",
+                profile
+            )),
+        }
+    }
+}
+
+pub trait Modem {
+    fn new(sample_rate: usize) -> Self
+    where
+        Self: Sized;
+    fn min_modulate_bytes(&self) -> usize;
+    fn prefered_payload_bytes(&self) -> usize;
+    fn preamble_frequency_range(&self) -> (f32, f32);
     fn modulate(&self, bytes: &[u8]) -> Vec<FP>;
     fn demodulate(&self, samples: &[FP]) -> Vec<u8>;
+    // One log-likelihood ratio ln(P(0) / P(1)) per bit, in the bit order of
+    // `BitByteConverter::bytes_to_bits`, a value near zero marks an erasure.
+    fn demodulate_soft(&self, samples: &[FP]) -> Vec<f32>;
+
+    fn channel_snr(&self, _samples: &[FP]) -> Option<Vec<f32>> {
+        None
+    }
+
+    // Modems with per-carrier constellations take a new allocation at run
+    // time, the packet length in samples stays the same. A check leaves the
+    // modem as it is and gives the bytes a packet would carry.
+    fn bit_loading(&self) -> Option<BitLoading> {
+        None
+    }
+
+    fn check_bit_loading(&self, _bit_loading: &BitLoading) -> Result<usize, String> {
+        Err("The modem has no bit loading".to_string())
+    }
+
+    fn set_bit_loading(&mut self, _bit_loading: BitLoading) -> Result<(), String> {
+        Err("The modem has no bit loading".to_string())
+    }
 }
 
 pub struct BitByteConverter;
@@ -43,11 +121,38 @@ impl BitByteConverter {
         }
         bytes
     }
+
+    pub fn llrs_to_bytes(llrs: &[f32]) -> Vec<u8> {
+        let bits = llrs
+            .iter()
+            .map(|llr| (*llr < 0.0) as u8)
+            .collect::<Vec<_>>();
+        Self::bits_to_bytes(&bits)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::ops::Range;
+
+    // Silences the given samples and checks that the given bits come out
+    // erased while every bit after them keeps a confident LLR.
+    pub(super) fn assert_erasure(
+        modem: &dyn Modem,
+        mut samples: Vec<FP>,
+        silenced: Range<usize>,
+        erased: Range<usize>,
+    ) -> Vec<f32> {
+        samples[silenced].fill(FP::ZERO);
+
+        let llrs = modem.demodulate_soft(&samples);
+        (erased.start..llrs.len()).for_each(|bit| match erased.contains(&bit) {
+            true => assert_eq!(llrs[bit], 0.0, "bit {} should be erased", bit),
+            false => assert!(llrs[bit].abs() > 1.0, "bit {} should be decided", bit),
+        });
+        llrs
+    }
 
     #[test]
     fn test_bit_byte_converter() {
@@ -1,192 +1,617 @@
-use super::{BitByteConverter, Modem};
+use super::{BitByteConverter, BitLoading, Constellation, LinkProfile, Modem};
 use crate::number::FP;
 use rustfft::FftDirection::{Forward, Inverse};
 use rustfft::{algorithm::Radix4, num_complex::Complex, Fft};
 
-cfg_if::cfg_if! {
-    if #[cfg(feature = "cable_link")] {
-        const BIT_PER_SYMBOL: usize = 20;
-        const DATA_SAMPLES: usize = 64;
-        const START_SUB_CARRIER_INDEX: usize = 2;
-        const CYCLIC_PREFIX_SAMPLES: usize = 0;
-    } else {
-        const BIT_PER_SYMBOL: usize = 4;
-        const DATA_SAMPLES: usize = 128;
-        const START_SUB_CARRIER_INDEX: usize = 18;
-        const CYCLIC_PREFIX_SAMPLES: usize = 12;
+const FFT_ENERGY_ZOOM: f32 = 1.0 / 4.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfdmConfig {
+    pub sub_carrier_count: usize,
+    pub pilot_sub_carriers: Vec<usize>,
+    pub constellation: Constellation,
+    pub bit_loading: Option<BitLoading>,
+    pub data_samples: usize,
+    pub start_sub_carrier_index: usize,
+    pub cyclic_prefix_samples: usize,
+    pub data_symbol_per_packet: usize,
+    pub prefered_payload_bytes: usize,
+    pub preamble_frequency_range: (f32, f32),
+}
+
+impl OfdmConfig {
+    pub fn cable() -> Self {
+        Self {
+            sub_carrier_count: 22,
+            pilot_sub_carriers: vec![0, 21],
+            constellation: Constellation::Qam16,
+            bit_loading: None,
+            data_samples: 64,
+            start_sub_carrier_index: 2,
+            cyclic_prefix_samples: 0,
+            data_symbol_per_packet: 24,
+            prefered_payload_bytes: 240,
+            preamble_frequency_range: (1600.0, 3200.0),
+        }
+    }
+
+    pub fn air() -> Self {
+        Self {
+            sub_carrier_count: 6,
+            pilot_sub_carriers: vec![0, 5],
+            constellation: Constellation::Bpsk,
+            bit_loading: None,
+            data_samples: 128,
+            start_sub_carrier_index: 18,
+            cyclic_prefix_samples: 12,
+            data_symbol_per_packet: 48,
+            prefered_payload_bytes: 48,
+            preamble_frequency_range: (3600.0, 5200.0),
+        }
+    }
+
+    fn data_sub_carriers(&self) -> Vec<usize> {
+        (0..self.sub_carrier_count)
+            .filter(|index| !self.pilot_sub_carriers.contains(index))
+            .map(|index| self.start_sub_carrier_index + index)
+            .collect()
+    }
+
+    fn bit_loading(&self) -> BitLoading {
+        self.bit_loading.clone().unwrap_or_else(|| {
+            let data_carrier_count = self.sub_carrier_count - self.pilot_sub_carriers.len();
+            BitLoading::uniform(data_carrier_count, self.constellation)
+        })
+    }
+
+    fn samples_per_symbol(&self) -> usize {
+        self.data_samples + self.cyclic_prefix_samples
+    }
+
+    fn packet_samples(&self) -> usize {
+        (self.data_symbol_per_packet + 1) * self.samples_per_symbol()
+    }
+
+    fn packet_data_bytes(&self) -> usize {
+        self.bit_loading().bit_per_symbol() * self.data_symbol_per_packet / 8
     }
 }
 
-const FFT_ENERGY_ZOOM: f32 = 1.0 / 4.0;
-const DATA_SYMBOL_PER_PACKET: usize = 24;
-const SAMPLES_PER_SYMBOL: usize = DATA_SAMPLES + CYCLIC_PREFIX_SAMPLES;
-const SYMBOL_PER_PACKET: usize = DATA_SYMBOL_PER_PACKET + 1;
-const PACKET_SAMPLES: usize = SYMBOL_PER_PACKET * SAMPLES_PER_SYMBOL;
-const PACKET_DATA_BYTES: usize = BIT_PER_SYMBOL * DATA_SYMBOL_PER_PACKET / 8;
+impl From<LinkProfile> for OfdmConfig {
+    fn from(profile: LinkProfile) -> Self {
+        match profile {
+            LinkProfile::Cable => Self::cable(),
+            LinkProfile::Air => Self::air(),
+        }
+    }
+}
+
+impl Default for OfdmConfig {
+    fn default() -> Self {
+        LinkProfile::default().into()
+    }
+}
 
 pub struct Ofdm {
-    standard_phase: [Complex<f32>; 2],
+    config: OfdmConfig,
+    bit_loading: BitLoading,
+    data_bins: Vec<usize>,
+    pilot_bins: Vec<usize>,
     ffts: [Radix4<f32>; 2],
 }
 
-impl Modem for Ofdm {
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "cable_link")] {
-            const PREFERED_PAYLOAD_BYTES: usize = 120;
-            const PREAMBLE_FREQUENCY_RANGE: (f32, f32) = (1600.0, 3200.0);
-        } else {
-            const PREFERED_PAYLOAD_BYTES: usize = 48;
-            const PREAMBLE_FREQUENCY_RANGE: (f32, f32) = (3600.0, 5200.0);
-        }
+impl Default for Ofdm {
+    fn default() -> Self {
+        Self::with_config(OfdmConfig::default())
     }
-    const MIN_MODULATE_BYTES: usize = PACKET_DATA_BYTES;
+}
 
+impl Modem for Ofdm {
     fn new(_: usize) -> Self {
-        let ffts = [
-            Radix4::new(DATA_SAMPLES, Forward),
-            Radix4::new(DATA_SAMPLES, Inverse),
-        ];
+        Self::with_config(OfdmConfig::default())
+    }
 
-        let standard_phase = [
-            Complex::new(FFT_ENERGY_ZOOM, 0.0),
-            Complex::new(-FFT_ENERGY_ZOOM, 0.0),
-        ];
+    fn min_modulate_bytes(&self) -> usize {
+        self.config.packet_data_bytes()
+    }
 
-        Self {
-            standard_phase,
-            ffts,
-        }
+    fn prefered_payload_bytes(&self) -> usize {
+        self.config.prefered_payload_bytes
+    }
+
+    fn preamble_frequency_range(&self) -> (f32, f32) {
+        self.config.preamble_frequency_range
     }
 
     fn modulate(&self, bytes: &[u8]) -> Vec<FP> {
+        let packet_data_bytes = self.config.packet_data_bytes();
+
         assert!(
-            bytes.len() % PACKET_DATA_BYTES == 0,
+            bytes.len().is_multiple_of(packet_data_bytes),
             "Bad data length: {}, can only modulate N * {} bytes per time!",
             bytes.len(),
-            PACKET_DATA_BYTES
+            packet_data_bytes
         );
 
         bytes
-            .chunks(PACKET_DATA_BYTES)
+            .chunks(packet_data_bytes)
             .flat_map(|chunk| self.encode_packet(chunk))
             .collect()
     }
 
     fn demodulate(&self, samples: &[FP]) -> Vec<u8> {
+        let data_bits = self
+            .packets(samples)
+            .flat_map(|chunk| self.equalize_packet(chunk))
+            .flat_map(|(constellation, point, _)| constellation.demap(point))
+            .collect::<Vec<_>>();
+
+        BitByteConverter::bits_to_bytes(&data_bits)
+    }
+
+    fn demodulate_soft(&self, samples: &[FP]) -> Vec<f32> {
+        self.packets(samples)
+            .flat_map(|chunk| self.equalize_packet(chunk))
+            .flat_map(|(constellation, point, snr)| constellation.soft_demap(point, snr))
+            .collect()
+    }
+
+    fn channel_snr(&self, samples: &[FP]) -> Option<Vec<f32>> {
+        let train_response = self.spectrum(&samples[..self.config.samples_per_symbol()]);
+        let snr = self
+            .carrier_snr(&train_response)
+            .iter()
+            .map(|snr| 10.0 * snr.log10())
+            .collect();
+
+        Some(snr)
+    }
+
+    fn bit_loading(&self) -> Option<BitLoading> {
+        Some(self.bit_loading.clone())
+    }
+
+    fn check_bit_loading(&self, bit_loading: &BitLoading) -> Result<usize, String> {
+        if bit_loading.carriers().len() != self.data_bins.len() {
+            return Err(format!(
+                "Bit loading covers {} subcarriers, the modem has {}",
+                bit_loading.carriers().len(),
+                self.data_bins.len()
+            ));
+        }
+        if bit_loading.bit_per_symbol() == 0 {
+            return Err("Bit loading must keep at least one subcarrier on".to_string());
+        }
+        let packet_bits = bit_loading.bit_per_symbol() * self.config.data_symbol_per_packet;
+        if !packet_bits.is_multiple_of(8) {
+            return Err(format!(
+                "Bit loading leaves {} bits per packet, not whole bytes",
+                packet_bits
+            ));
+        }
+
+        Ok(packet_bits / 8)
+    }
+
+    fn set_bit_loading(&mut self, bit_loading: BitLoading) -> Result<(), String> {
+        self.check_bit_loading(&bit_loading)?;
+        self.config.bit_loading = Some(bit_loading.clone());
+        self.bit_loading = bit_loading;
+        Ok(())
+    }
+}
+
+impl Ofdm {
+    pub fn with_config(config: OfdmConfig) -> Self {
+        assert!(
+            config.start_sub_carrier_index + config.sub_carrier_count <= config.data_samples / 2,
+            "Subcarriers must stay below the Nyquist bin!"
+        );
+
+        assert!(
+            config
+                .pilot_sub_carriers
+                .iter()
+                .all(|index| *index < config.sub_carrier_count),
+            "Pilots must be among the subcarriers!"
+        );
+
+        let data_bins = config.data_sub_carriers();
+        let pilot_bins = config
+            .pilot_sub_carriers
+            .iter()
+            .map(|index| config.start_sub_carrier_index + index)
+            .collect();
+
+        let bit_loading = config.bit_loading();
+        assert!(
+            bit_loading.carriers().len() == data_bins.len(),
+            "Bit loading must cover every data subcarrier!"
+        );
+        assert!(
+            bit_loading.bit_per_symbol() > 0,
+            "Bit loading must keep at least one subcarrier on!"
+        );
+        // Packets are filled bit by bit, a byte cut by the packet end would
+        // leave the last symbol short.
+        let packet_bits = bit_loading.bit_per_symbol() * config.data_symbol_per_packet;
+        assert!(
+            packet_bits > 0 && packet_bits.is_multiple_of(8),
+            "Every packet must carry whole bytes!"
+        );
+
+        let ffts = [
+            Radix4::new(config.data_samples, Forward),
+            Radix4::new(config.data_samples, Inverse),
+        ];
+
+        Self {
+            config,
+            bit_loading,
+            data_bins,
+            pilot_bins,
+            ffts,
+        }
+    }
+
+    fn packets<'a>(&self, samples: &'a [FP]) -> std::slice::Chunks<'a, FP> {
+        let packet_samples = self.config.packet_samples();
+
         assert!(
-            samples.len() % PACKET_SAMPLES == 0,
+            samples.len().is_multiple_of(packet_samples),
             "Bad data length: {}, can only demodulate N * {} samples per time!",
             samples.len(),
-            PACKET_SAMPLES
+            packet_samples
         );
 
-        let data_bytes = samples
-            .chunks(PACKET_SAMPLES)
-            .flat_map(|chunk| self.decode_packet(chunk))
+        samples.chunks(packet_samples)
+    }
+
+    fn carrier_snr(&self, train_response: &[Complex<f32>]) -> Vec<f32> {
+        let config = &self.config;
+
+        // Bins between DC and Nyquist that carry nothing only pick up the
+        // noise floor, the training symbol on every carrier gives its level.
+        let carriers = config.start_sub_carrier_index
+            ..config.start_sub_carrier_index + config.sub_carrier_count;
+        let noise_bins = (1..config.data_samples / 2)
+            .filter(|index| !carriers.contains(index))
+            .map(|index| train_response[index].norm_sqr())
             .collect::<Vec<_>>();
+        let noise = noise_bins.iter().sum::<f32>() / noise_bins.len().max(1) as f32;
 
-        BitByteConverter::bits_to_bytes(&data_bytes)
+        self.data_bins
+            .iter()
+            .map(|bin| train_response[*bin].norm_sqr() / noise.max(f32::MIN_POSITIVE))
+            .collect()
     }
-}
 
-impl Ofdm {
-    fn encode_packet(&self, chunk: &[u8]) -> Vec<FP> {
-        let bits = {
-            let train_empty_bits = [0u8; BIT_PER_SYMBOL];
-            let data_bits = BitByteConverter::bytes_to_bits(chunk);
+    fn spectrum(&self, symbol: &[FP]) -> Vec<Complex<f32>> {
+        // Starting halfway into the cyclic prefix leaves room for a detector
+        // that locks a little late, the extra phase ramp equalizes out.
+        let start = self.config.cyclic_prefix_samples / 2;
+        let mut buffer = symbol[start..start + self.config.data_samples]
+            .iter()
+            .map(|x| Complex::new(FP::into(*x), 0.0))
+            .collect::<Vec<_>>();
 
-            train_empty_bits
-                .iter()
-                .chain(data_bits.iter())
-                .cloned()
-                .collect::<Vec<_>>()
-        };
+        self.ffts[0].process(&mut buffer);
+        buffer
+    }
 
-        bits.chunks(BIT_PER_SYMBOL)
-            .flat_map(|chunk| {
-                let mut buffer = vec![Complex::default(); DATA_SAMPLES];
+    fn encode_packet(&self, chunk: &[u8]) -> Vec<FP> {
+        let config = &self.config;
+        let reference = Complex::new(FFT_ENERGY_ZOOM, 0.0);
+
+        // The training symbol carries the same known point on every subcarrier,
+        // switched off ones included, so the receiver keeps measuring their
+        // gain, phase and SNR. Pilots repeat it in every data symbol.
+        let mut train_symbol = vec![Complex::default(); config.data_samples];
+        train_symbol
+            .iter_mut()
+            .skip(config.start_sub_carrier_index)
+            .take(config.sub_carrier_count)
+            .for_each(|bin| *bin = reference);
+
+        let data_bits = BitByteConverter::bytes_to_bits(chunk);
+        let data_symbols = data_bits
+            .chunks(self.bit_loading.bit_per_symbol())
+            .map(|bits| {
+                let mut buffer = vec![Complex::default(); config.data_samples];
+                self.pilot_bins
+                    .iter()
+                    .for_each(|bin| buffer[*bin] = reference);
 
+                let mut offset = 0;
+                self.data_bins
+                    .iter()
+                    .zip(self.bit_loading.carriers())
+                    .for_each(|(bin, carrier)| {
+                        if let Some(constellation) = carrier {
+                            let point_bits = constellation.bit_per_point();
+                            let point = constellation.map(&bits[offset..offset + point_bits]);
+                            buffer[*bin] = point * FFT_ENERGY_ZOOM;
+                            offset += point_bits;
+                        }
+                    });
                 buffer
-                    .iter_mut()
-                    .skip(START_SUB_CARRIER_INDEX)
-                    .zip(chunk.iter())
-                    .for_each(|(buffer, bit)| *buffer = self.standard_phase[*bit as usize]);
+            });
 
+        std::iter::once(train_symbol)
+            .chain(data_symbols)
+            .flat_map(|mut buffer| {
                 self.ffts[1].process(&mut buffer);
 
                 buffer
                     .iter()
                     .map(|x| FP::from(x.re))
-                    .skip(DATA_SAMPLES - CYCLIC_PREFIX_SAMPLES)
+                    .skip(config.data_samples - config.cyclic_prefix_samples)
                     .chain(buffer.iter().map(|x| FP::from(x.re)))
                     .collect::<Vec<_>>()
             })
             .collect::<Vec<_>>()
     }
 
-    fn decode_packet(&self, chunk: &[FP]) -> Vec<u8> {
-        let (train_samples, data_samples) = chunk.split_at(SAMPLES_PER_SYMBOL);
-
-        let train_args = {
-            let mut buffer = train_samples[CYCLIC_PREFIX_SAMPLES..]
-                .iter()
-                .map(|x| Complex::new(FP::into(*x), 0.0))
-                .collect::<Vec<_>>();
+    fn equalize_packet(&self, chunk: &[FP]) -> Vec<(Constellation, Complex<f32>, f32)> {
+        let config = &self.config;
+        let (train_samples, data_samples) = chunk.split_at(config.samples_per_symbol());
 
-            self.ffts[0].process(&mut buffer);
-
-            (0..BIT_PER_SYMBOL)
-                .map(|index| buffer[START_SUB_CARRIER_INDEX + index].arg())
-                .collect::<Vec<_>>()
-        };
+        let train_response = self.spectrum(train_samples);
+        let carrier_snr = self.carrier_snr(&train_response);
+        let mut phase_tracker = PhaseTracker::default();
 
         data_samples
-            .chunks(SAMPLES_PER_SYMBOL)
+            .chunks(config.samples_per_symbol())
             .flat_map(|chunk| {
-                let mut buffer = vec![Complex::default(); DATA_SAMPLES];
+                let buffer = self.spectrum(chunk);
+                let equalized = |bin: usize| buffer[bin] / train_response[bin];
 
-                buffer
-                    .iter_mut()
-                    .zip(chunk[CYCLIC_PREFIX_SAMPLES..].iter())
-                    .for_each(|(x, y)| *x = Complex::new(FP::into(*y), 0.0));
-
-                self.ffts[0].process(&mut buffer);
+                phase_tracker.update(self.pilot_bins.iter().map(|&bin| (bin, equalized(bin))));
 
-                (0..BIT_PER_SYMBOL)
-                    .map(|index| {
-                        let offset = Complex::exp(-Complex::new(0.0, 1.0) * train_args[index]);
-                        ((buffer[START_SUB_CARRIER_INDEX + index] * offset).re < 0.0) as u8
+                self.data_bins
+                    .iter()
+                    .zip(self.bit_loading.carriers())
+                    .zip(carrier_snr.iter())
+                    .filter_map(|((bin, carrier), snr)| {
+                        let point = equalized(*bin) * phase_tracker.correction(*bin);
+                        carrier.map(|constellation| (constellation, point, *snr))
                     })
                     .collect::<Vec<_>>()
             })
-            .collect::<Vec<_>>()
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct PhaseTracker {
+    common: f32,
+    slope: f32,
+}
+
+impl PhaseTracker {
+    fn phase(&self, bin: usize) -> f32 {
+        self.common + self.slope * bin as f32
+    }
+
+    fn correction(&self, bin: usize) -> Complex<f32> {
+        Complex::from_polar(1.0, -self.phase(bin))
+    }
+
+    fn update(&mut self, pilots: impl Iterator<Item = (usize, Complex<f32>)>) {
+        // Pilots are measured against the previous estimate, only the small
+        // change since the last symbol is seen and nothing wraps around.
+        let pilots = pilots
+            .map(|(bin, pilot)| {
+                let residual = (pilot * self.correction(bin)).arg();
+                (bin as f32, self.phase(bin) + residual)
+            })
+            .collect::<Vec<_>>();
+
+        if pilots.is_empty() {
+            return;
+        }
+
+        // A common phase error turns every carrier alike while a timing drift
+        // adds a ramp across them, a line through the pilots gives both.
+        let count = pilots.len() as f32;
+        let mean_bin = pilots.iter().map(|(bin, _)| bin).sum::<f32>() / count;
+        let mean_phase = pilots.iter().map(|(_, phase)| phase).sum::<f32>() / count;
+        let spread = pilots
+            .iter()
+            .map(|(bin, _)| (bin - mean_bin).powi(2))
+            .sum::<f32>();
+
+        if spread > 0.0 {
+            self.slope = pilots
+                .iter()
+                .map(|(bin, phase)| (bin - mean_bin) * (phase - mean_phase))
+                .sum::<f32>()
+                / spread;
+        }
+        self.common = mean_phase - self.slope * mean_bin;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::modem::tests::assert_erasure;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
     const TEST_SEQUENCE_BYTES: usize = 216;
 
-    #[test]
-    fn test_ofdm() {
-        let data = (0..TEST_SEQUENCE_BYTES)
-            .map(|index| index as u8)
-            .collect::<Vec<_>>();
+    fn round_trip(config: OfdmConfig) {
+        // Denser constellations get proportionally quieter noise, their
+        // decision regions shrink with every extra bit per point.
+        let noise = 0.5 / config.constellation.bit_per_point() as f32;
+        let mut rng = StdRng::seed_from_u64(0);
 
-        let ofdm = Ofdm::new(0);
+        let ofdm = Ofdm::with_config(config);
+        let data_bytes = TEST_SEQUENCE_BYTES.next_multiple_of(ofdm.min_modulate_bytes());
+
+        let data = (0..data_bytes).map(|index| index as u8).collect::<Vec<_>>();
 
         let mut modulated = ofdm.modulate(&data);
         println!("Modulated data samples: {:?}", modulated.len());
 
         modulated
             .iter_mut()
-            .for_each(|sample| *sample += FP::from(rand::random::<f32>()) / FP::from(2.0));
+            .for_each(|sample| *sample += FP::from((rng.gen::<f32>() - 0.5) * noise));
 
         let demodulated = ofdm.demodulate(&modulated);
         println!("Demodulated data bytes: {:?}", demodulated.len());
 
         assert_eq!(data, demodulated);
     }
+
+    #[test]
+    fn test_ofdm() {
+        for config in [OfdmConfig::cable(), OfdmConfig::air()] {
+            round_trip(config);
+        }
+    }
+
+    #[test]
+    fn test_ofdm_late_lock() {
+        // Under echoes the detector peaks a few samples after the preamble
+        // ends, the payload then arrives early by that much.
+        let config = OfdmConfig {
+            constellation: Constellation::Qam64,
+            ..OfdmConfig::air()
+        };
+        let late = config.cyclic_prefix_samples / 2 - 1;
+        let ofdm = Ofdm::with_config(config);
+
+        let data = (0..ofdm.min_modulate_bytes())
+            .map(|index| index as u8)
+            .collect::<Vec<_>>();
+
+        let mut modulated = ofdm.modulate(&data);
+        modulated.rotate_left(late);
+        assert_eq!(ofdm.demodulate(&modulated), data);
+    }
+
+    #[test]
+    fn test_ofdm_constellation() {
+        for constellation in [
+            Constellation::Bpsk,
+            Constellation::Qpsk,
+            Constellation::Qam16,
+            Constellation::Qam64,
+        ] {
+            for config in [OfdmConfig::cable(), OfdmConfig::air()] {
+                let config = OfdmConfig {
+                    constellation,
+                    ..config
+                };
+                let data_carrier_count = config.sub_carrier_count - config.pilot_sub_carriers.len();
+                assert_eq!(
+                    Ofdm::with_config(config.clone()).min_modulate_bytes(),
+                    data_carrier_count
+                        * constellation.bit_per_point()
+                        * config.data_symbol_per_packet
+                        / 8
+                );
+                round_trip(config);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ofdm_bit_loading() {
+        let carriers = [
+            None,
+            Some(Constellation::Bpsk),
+            Some(Constellation::Qpsk),
+            Some(Constellation::Qam16),
+            Some(Constellation::Qam64),
+        ];
+        let bit_loading = BitLoading::from_bytes(&[0, 1, 2, 4, 6].repeat(4)).unwrap();
+        assert_eq!(bit_loading.carriers()[..5], carriers);
+
+        // The uniform constellation is ignored, it only sets the test noise.
+        let config = OfdmConfig {
+            constellation: Constellation::Qam64,
+            bit_loading: Some(bit_loading),
+            ..OfdmConfig::cable()
+        };
+        let ofdm = Ofdm::with_config(config.clone());
+        assert_eq!(ofdm.min_modulate_bytes(), 13 * 4 * 3);
+        round_trip(config);
+
+        let snr = ofdm.channel_snr(&ofdm.modulate(&[0; 156])).unwrap();
+        assert_eq!(snr.len(), 20);
+        assert!(snr.iter().all(|snr| *snr > 60.0));
+
+        // A running modem takes the same allocation and keeps its packets.
+        let mut reloaded = Ofdm::with_config(OfdmConfig::cable());
+        let packet_samples = reloaded.modulate(&[0; 240]).len();
+        let loaded = ofdm.bit_loading().unwrap();
+        assert_eq!(reloaded.check_bit_loading(&loaded), Ok(156));
+        assert_eq!(reloaded.min_modulate_bytes(), 240);
+        reloaded.set_bit_loading(loaded).unwrap();
+        assert_eq!(reloaded.min_modulate_bytes(), ofdm.min_modulate_bytes());
+        assert_eq!(reloaded.modulate(&[0; 156]).len(), packet_samples);
+
+        let off = BitLoading::from_bytes(&[0; 20]).unwrap();
+        assert!(reloaded.set_bit_loading(off).is_err());
+        let short = BitLoading::from_bytes(&[4; 19]).unwrap();
+        assert!(reloaded.set_bit_loading(short).is_err());
+        assert_eq!(reloaded.bit_loading(), ofdm.bit_loading());
+
+        // Twenty symbols of a single BPSK carrier end mid byte.
+        let mut odd = Ofdm::with_config(OfdmConfig {
+            data_symbol_per_packet: 20,
+            ..OfdmConfig::cable()
+        });
+        let mut single = [0; 20];
+        single[0] = 1;
+        let single = BitLoading::from_bytes(&single).unwrap();
+        assert!(odd.check_bit_loading(&single).is_err());
+        assert!(odd.set_bit_loading(single).is_err());
+    }
+
+    #[test]
+    fn test_ofdm_soft() {
+        let config = OfdmConfig {
+            constellation: Constellation::Bpsk,
+            ..OfdmConfig::cable()
+        };
+        let ofdm = Ofdm::with_config(config.clone());
+        let data = (0..ofdm.min_modulate_bytes() * 2)
+            .map(|index| index as u8)
+            .collect::<Vec<_>>();
+
+        let mut modulated = ofdm.modulate(&data);
+        modulated
+            .iter_mut()
+            .for_each(|sample| *sample += FP::from(rand::random::<f32>() / 4.0));
+
+        // A symbol lost to a dropout leaves its bits undecided rather than wrong.
+        let symbol_samples = config.samples_per_symbol();
+        let silenced = symbol_samples..symbol_samples * 2;
+        let llrs = assert_erasure(&ofdm, modulated, silenced, 0..20);
+        assert_eq!(llrs.len(), data.len() * 8);
+        assert_eq!(BitByteConverter::llrs_to_bytes(&llrs)[3..], data[3..]);
+
+        let llrs = ofdm.demodulate_soft(&ofdm.modulate(&data));
+        assert_eq!(BitByteConverter::llrs_to_bytes(&llrs), data);
+    }
+
+    #[test]
+    fn test_phase_tracker() {
+        let mut phase_tracker = PhaseTracker::default();
+
+        // The ramp steepens every symbol until it wraps several times over
+        // the band, each step alone stays well inside half a turn.
+        for symbol in 1..=40 {
+            let phase = |bin: usize| 0.2 * symbol as f32 + 0.03 * symbol as f32 * bin as f32;
+            let pilots = [2, 23].map(|bin| (bin, Complex::from_polar(1.0, phase(bin))));
+            phase_tracker.update(pilots.into_iter());
+
+            let residual = Complex::from_polar(1.0, phase(12)) * phase_tracker.correction(12);
+            assert!(residual.arg().abs() < 1e-3);
+        }
+    }
 }
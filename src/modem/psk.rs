@@ -1,104 +1,343 @@
-use super::{BitByteConverter, Modem};
+use super::{BitByteConverter, LinkProfile, Modem};
 use crate::number::FP;
+use std::str::FromStr;
 
-cfg_if::cfg_if! {
-    if #[cfg(feature = "cable_link")] {
-        const BIT_PER_SYMBOL: usize = 1;
-        const BIT_RATE_MUL_RATIO: usize = 1250;
-        const CARRIER_FREQUENCY: f32 = 1600.0;
-    } else {
-        const BIT_PER_SYMBOL: usize = 1;
-        const BIT_RATE_MUL_RATIO: usize = 1250;
-        const CARRIER_FREQUENCY: f32 = 1600.0;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PskOrder {
+    #[default]
+    Bpsk,
+    Qpsk,
+    Psk8,
+}
+
+impl PskOrder {
+    pub fn bit_per_symbol(&self) -> usize {
+        match self {
+            Self::Bpsk => 1,
+            Self::Qpsk => 2,
+            Self::Psk8 => 3,
+        }
+    }
+}
+
+impl FromStr for PskOrder {
+    type Err = String;
+
+    fn from_str(order: &str) -> Result<Self, Self::Err> {
+        match order {
+            "bpsk" => Ok(Self::Bpsk),
+            "qpsk" => Ok(Self::Qpsk),
+            "8psk" => Ok(Self::Psk8),
+            _ => Err(format!(
+                "Unknown PSK order \"{}\", use bpsk, qpsk or 8psk",
+                order
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PskConfig {
+    pub order: PskOrder,
+    pub differential: bool,
+    pub symbol_rate: usize,
+    pub carrier_frequency: f32,
+    pub prefered_payload_bytes: usize,
+    pub preamble_frequency_range: (f32, f32),
+}
+
+impl PskConfig {
+    pub fn cable() -> Self {
+        Self {
+            order: PskOrder::Bpsk,
+            differential: false,
+            symbol_rate: 1250,
+            carrier_frequency: 1600.0,
+            prefered_payload_bytes: 16,
+            preamble_frequency_range: (900.0, 3000.0),
+        }
+    }
+
+    pub fn air() -> Self {
+        Self::cable()
+    }
+
+    fn chunk_variance(&self) -> usize {
+        2usize.pow(self.order.bit_per_symbol() as u32)
+    }
+
+    fn symbol_samples(&self, sample_rate: usize) -> usize {
+        sample_rate / self.symbol_rate
+    }
+}
+
+impl From<LinkProfile> for PskConfig {
+    fn from(profile: LinkProfile) -> Self {
+        match profile {
+            LinkProfile::Cable => Self::cable(),
+            LinkProfile::Air => Self::air(),
+        }
     }
 }
 
-const SYMBOL_RATE: usize = BIT_RATE_MUL_RATIO * BIT_PER_SYMBOL / BIT_PER_SYMBOL;
-const CHUNK_VARIANCE: usize = 2usize.pow(BIT_PER_SYMBOL as u32);
+impl Default for PskConfig {
+    fn default() -> Self {
+        LinkProfile::default().into()
+    }
+}
 
 pub struct Psk {
+    config: PskConfig,
     sample_rate: usize,
-    standard_chunk: [Vec<FP>; CHUNK_VARIANCE],
-    gray_code: [Vec<u8>; CHUNK_VARIANCE],
+    standard_chunk: Vec<Vec<FP>>,
+    gray_code: Vec<Vec<u8>>,
+    phases: Vec<(f32, f32)>,
+    iq_weights: [Vec<f32>; 2],
 }
 
 impl Modem for Psk {
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "cable_link")] {
-            const PREFERED_PAYLOAD_BYTES: usize = 16;
-            const PREAMBLE_FREQUENCY_RANGE: (f32, f32) = (900.0, 3000.0);
-        } else {
-            const PREFERED_PAYLOAD_BYTES: usize = 16;
-            const PREAMBLE_FREQUENCY_RANGE: (f32, f32) = (900.0, 3000.0);
-        }
+    fn new(sample_rate: usize) -> Self {
+        Self::with_config(sample_rate, PskConfig::default())
     }
-    const MIN_MODULATE_BYTES: usize = BIT_PER_SYMBOL;
 
-    fn new(sample_rate: usize) -> Self {
-        let gray_code = Self::gray_code(BIT_PER_SYMBOL);
-        let standard_chunk = Self::standard_chunk(sample_rate);
+    fn min_modulate_bytes(&self) -> usize {
+        // A single symbol is too short to carry the frame header, so a whole
+        // payload goes behind each preamble.
+        self.config.prefered_payload_bytes
+    }
 
-        Self {
-            sample_rate,
-            standard_chunk: standard_chunk.try_into().unwrap(),
-            gray_code: gray_code.try_into().unwrap(),
-        }
+    fn prefered_payload_bytes(&self) -> usize {
+        self.config.prefered_payload_bytes
+    }
+
+    fn preamble_frequency_range(&self) -> (f32, f32) {
+        self.config.preamble_frequency_range
     }
 
     fn modulate(&self, bytes: &[u8]) -> Vec<FP> {
-        BitByteConverter::bytes_to_bits(bytes)
-            .chunks(BIT_PER_SYMBOL)
-            .flat_map(|chunk| {
-                let index = self
-                    .gray_code
-                    .iter()
-                    .enumerate()
-                    .find(|(_, code)| code == &chunk)
-                    .unwrap()
-                    .0;
-
-                self.standard_chunk[index as usize].clone()
-            })
+        let bit_per_symbol = self.config.order.bit_per_symbol();
+
+        // The last symbol is padded with zeros when the bits do not fill it.
+        let mut bits = BitByteConverter::bytes_to_bits(bytes);
+        bits.resize(bits.len().next_multiple_of(bit_per_symbol), 0);
+
+        let indices = bits.chunks(bit_per_symbol).map(|chunk| {
+            self.gray_code
+                .iter()
+                .enumerate()
+                .find(|(_, code)| code == &chunk)
+                .unwrap()
+                .0
+        });
+
+        // Differential symbols advance the phase of the previous one instead
+        // of setting it, after a reference symbol at phase zero.
+        let indices = match self.config.differential {
+            true => {
+                let variance = self.config.chunk_variance();
+                std::iter::once(0)
+                    .chain(indices.scan(0, |phase, index| {
+                        *phase = (*phase + index) % variance;
+                        Some(*phase)
+                    }))
+                    .collect::<Vec<_>>()
+            }
+            false => indices.collect(),
+        };
+
+        indices
+            .iter()
+            .flat_map(|index| self.standard_chunk[*index].clone())
             .collect()
     }
 
     fn demodulate(&self, samples: &[FP]) -> Vec<u8> {
-        let chunk_length = self.sample_rate / SYMBOL_RATE;
-
-        let bits = samples
-            .chunks(chunk_length)
-            .flat_map(|chunk| {
-                let similarities = self
-                    .standard_chunk
-                    .iter()
-                    .map(|standard| {
-                        chunk
-                            .iter()
-                            .zip(standard.iter())
-                            .map(|(a, b)| *a * *b)
-                            .sum::<FP>()
-                    })
-                    .collect::<Vec<_>>();
-
-                let max_index = similarities
-                    .iter()
-                    .enumerate()
-                    .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                    .unwrap()
-                    .0;
-
-                self.gray_code[max_index as usize]
-                    .iter()
-                    .cloned()
-                    .collect::<Vec<_>>()
+        let mut bits = self
+            .symbols(samples)
+            .iter()
+            .flat_map(|symbol| {
+                let nearest = self.nearest_phase(*symbol);
+                self.gray_code[nearest].iter().cloned()
             })
             .collect::<Vec<_>>();
 
+        bits.truncate(bits.len() / 8 * 8);
         BitByteConverter::bits_to_bytes(&bits)
     }
+
+    fn demodulate_soft(&self, samples: &[FP]) -> Vec<f32> {
+        let symbols = self.symbols(samples);
+
+        // The amplitude and the noise power are estimated over the whole block,
+        // from the phase every symbol lands closest to and how far it misses.
+        let (amplitude, noise) = {
+            let count = symbols.len().max(1) as f32;
+            let amplitude = symbols
+                .iter()
+                .map(|symbol| self.projection(*symbol, self.nearest_phase(*symbol)))
+                .sum::<f32>()
+                / count;
+            let noise = symbols
+                .iter()
+                .map(|symbol| self.distance(*symbol, amplitude, self.nearest_phase(*symbol)))
+                .sum::<f32>()
+                / count;
+            (amplitude, noise.max(f32::MIN_POSITIVE))
+        };
+
+        let bit_per_symbol = self.config.order.bit_per_symbol();
+        let mut llrs = symbols
+            .iter()
+            .flat_map(|symbol| {
+                (0..bit_per_symbol).map(move |bit| {
+                    let mut nearest = [f32::INFINITY; 2];
+                    self.gray_code.iter().enumerate().for_each(|(index, code)| {
+                        let nearest = &mut nearest[code[bit] as usize];
+                        *nearest = nearest.min(self.distance(*symbol, amplitude, index));
+                    });
+                    (nearest[1] - nearest[0]) / noise
+                })
+            })
+            .collect::<Vec<_>>();
+
+        llrs.truncate(llrs.len() / 8 * 8);
+        llrs
+    }
 }
 
 impl Psk {
+    pub fn with_config(sample_rate: usize, config: PskConfig) -> Self {
+        let symbol_cycles = config.symbol_samples(sample_rate) as f32 * config.carrier_frequency
+            / sample_rate as f32;
+        assert!(
+            symbol_cycles >= 1.0,
+            "A symbol must span at least one carrier cycle!"
+        );
+
+        let gray_code = Self::gray_code(config.order.bit_per_symbol());
+        let standard_chunk = Self::standard_chunk(sample_rate, &config);
+        let iq_weights = Self::iq_weights(sample_rate, &config);
+
+        let phases = (0..config.chunk_variance())
+            .map(|index| {
+                let phase = Self::start_phase(&config)
+                    + index as f32 * 2.0 * std::f32::consts::PI / config.chunk_variance() as f32;
+                (phase.cos(), phase.sin())
+            })
+            .collect();
+
+        Self {
+            config,
+            sample_rate,
+            standard_chunk,
+            gray_code,
+            phases,
+            iq_weights,
+        }
+    }
+
+    fn symbols(&self, samples: &[FP]) -> Vec<(f32, f32)> {
+        let symbols = samples
+            .chunks(self.config.symbol_samples(self.sample_rate))
+            .map(|chunk| {
+                let [in_phase, quadrature] = self.iq_weights.each_ref().map(|weights| {
+                    chunk
+                        .iter()
+                        .zip(weights.iter())
+                        .map(|(sample, weight)| FP::into::<f32>(*sample) * weight)
+                        .sum::<f32>()
+                });
+                (in_phase, quadrature)
+            })
+            .collect::<Vec<_>>();
+
+        if !self.config.differential {
+            return symbols;
+        }
+
+        // Each symbol is turned back by the phase of the one before it, which
+        // cancels any constant phase offset or polarity inversion of the link.
+        symbols
+            .windows(2)
+            .map(|pair| {
+                let [(previous_re, previous_im), (re, im)] = [pair[0], pair[1]];
+                let magnitude = previous_re.hypot(previous_im).max(f32::MIN_POSITIVE);
+                (
+                    (re * previous_re + im * previous_im) / magnitude,
+                    (im * previous_re - re * previous_im) / magnitude,
+                )
+            })
+            .collect()
+    }
+
+    fn projection(&self, symbol: (f32, f32), index: usize) -> f32 {
+        let (cos, sin) = self.phases[index];
+        symbol.0 * cos + symbol.1 * sin
+    }
+
+    fn distance(&self, symbol: (f32, f32), amplitude: f32, index: usize) -> f32 {
+        let (cos, sin) = self.phases[index];
+        (symbol.0 - amplitude * cos).powi(2) + (symbol.1 - amplitude * sin).powi(2)
+    }
+
+    fn nearest_phase(&self, symbol: (f32, f32)) -> usize {
+        (0..self.phases.len())
+            .max_by(|a, b| {
+                let a = self.projection(symbol, *a);
+                let b = self.projection(symbol, *b);
+                a.partial_cmp(&b).unwrap()
+            })
+            .unwrap()
+    }
+
+    fn iq_weights(sample_rate: usize, config: &PskConfig) -> [Vec<f32>; 2] {
+        // A symbol lasts only a cycle or so, too short for sine and cosine to
+        // be orthogonal, so both are fitted together with a DC term by least
+        // squares instead of being correlated one at a time.
+        let basis = (0..config.symbol_samples(sample_rate))
+            .map(|index| {
+                let phase = index as f64 / sample_rate as f64
+                    * 2.0
+                    * std::f64::consts::PI
+                    * config.carrier_frequency as f64;
+                [phase.sin(), phase.cos(), 1.0]
+            })
+            .collect::<Vec<_>>();
+
+        let mut gram = [[0.0; 3]; 3];
+        basis.iter().for_each(|row| {
+            (0..3).for_each(|i| (0..3).for_each(|j| gram[i][j] += row[i] * row[j]));
+        });
+        let inverse = Self::invert(gram);
+
+        [0, 1].map(|output| {
+            basis
+                .iter()
+                .map(|row| (0..3).map(|k| inverse[output][k] * row[k]).sum::<f64>() as f32)
+                .collect()
+        })
+    }
+
+    fn invert(m: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+        let cofactor = |i: usize, j: usize| {
+            let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+            let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+        let determinant = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum::<f64>();
+
+        let mut inverse = [[0.0; 3]; 3];
+        (0..3).for_each(|i| (0..3).for_each(|j| inverse[j][i] = cofactor(i, j) / determinant));
+        inverse
+    }
+
+    fn start_phase(config: &PskConfig) -> f32 {
+        match (config.differential, config.order) {
+            (true, _) | (_, PskOrder::Bpsk) => 0.0,
+            _ => std::f32::consts::PI / config.chunk_variance() as f32,
+        }
+    }
+
     fn gray_code(bits: usize) -> Vec<Vec<u8>> {
         let mut gray_code = vec![vec![0], vec![1]];
 
@@ -113,34 +352,31 @@ impl Psk {
         gray_code
     }
 
-    fn standard_chunk(sample_rate: usize) -> Vec<Vec<FP>> {
+    fn standard_chunk(sample_rate: usize, config: &PskConfig) -> Vec<Vec<FP>> {
+        let chunk_variance = config.chunk_variance();
+
         let sine_chunk = |length, phase| {
             (0..length)
                 .map(|index| {
-                    let result: FP = FP::from(index)
-                        / FP::from(sample_rate)
+                    let result: FP = FP::from(index) / FP::from(sample_rate)
                         * FP::from(2.0)
                         * FP::PI
-                        * FP::from(CARRIER_FREQUENCY)
+                        * FP::from(config.carrier_frequency)
                         + phase;
                     result.sin()
                 })
                 .collect::<Vec<_>>()
         };
 
-        let start_phase = if BIT_PER_SYMBOL == 1 {
-            FP::ZERO
-        } else {
-            FP::PI / FP::from(CHUNK_VARIANCE)
-        };
+        let start_phase = FP::from(Self::start_phase(config));
 
-        (0..2usize.pow(BIT_PER_SYMBOL as u32))
+        (0..chunk_variance)
             .map(|index| {
                 let round = FP::PI * FP::from(2.0);
-                let phase_slice = round / FP::from(CHUNK_VARIANCE);
-                
+                let phase_slice = round / FP::from(chunk_variance);
+
                 sine_chunk(
-                    sample_rate / SYMBOL_RATE,
+                    config.symbol_samples(sample_rate),
                     start_phase + FP::from(index) * phase_slice,
                 )
             })
@@ -151,6 +387,7 @@ impl Psk {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::modem::tests::assert_erasure;
 
     const SAMPLE_RATE: usize = 48000;
     const TEST_SEQUENCE_BYTES: usize = 1;
@@ -173,4 +410,110 @@ mod tests {
 
         assert_eq!(data, demodulated);
     }
+
+    #[test]
+    fn test_psk_soft() {
+        let data = (0..16).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        let psk = Psk::new(SAMPLE_RATE);
+
+        let mut modulated = psk.modulate(&data);
+        modulated
+            .iter_mut()
+            .for_each(|sample| *sample += FP::from(rand::random::<f32>()) / FP::from(2.0));
+
+        let llrs = psk.demodulate_soft(&modulated);
+        assert_eq!(llrs.len(), data.len() * 8);
+        assert_eq!(
+            BitByteConverter::llrs_to_bytes(&llrs),
+            psk.demodulate(&modulated)
+        );
+        assert_eq!(BitByteConverter::llrs_to_bytes(&llrs), data);
+
+        // A silenced symbol correlates with nothing, so its bits come out erased.
+        let symbol_samples = SAMPLE_RATE / psk.config.symbol_rate;
+        let bits = psk.config.order.bit_per_symbol();
+        assert_erasure(&psk, modulated, 0..symbol_samples, 0..bits);
+    }
+
+    #[test]
+    fn test_psk_order() {
+        // Each extra bit halves the angle between neighbouring phases, so the
+        // tolerated noise shrinks with the order.
+        for (order, noise) in [
+            (PskOrder::Bpsk, 1.0),
+            (PskOrder::Qpsk, 0.5),
+            (PskOrder::Psk8, 0.25),
+        ] {
+            let config = PskConfig {
+                order,
+                ..PskConfig::default()
+            };
+            let psk = Psk::with_config(SAMPLE_RATE, config);
+
+            let data = (0..psk.min_modulate_bytes() * 4)
+                .map(|_| rand::random::<u8>())
+                .collect::<Vec<_>>();
+
+            let mut modulated = psk.modulate(&data);
+            let symbols = (data.len() * 8).div_ceil(order.bit_per_symbol());
+            let symbol_samples = psk.config.symbol_samples(SAMPLE_RATE);
+            assert_eq!(modulated.len(), symbols * symbol_samples);
+
+            modulated.iter_mut().for_each(|sample| {
+                *sample += FP::from((rand::random::<f32>() - 0.5) * noise);
+            });
+
+            assert_eq!(psk.demodulate(&modulated), data, "{:?}", order);
+            let llrs = psk.demodulate_soft(&modulated);
+            assert_eq!(BitByteConverter::llrs_to_bytes(&llrs), data, "{:?}", order);
+        }
+
+        assert_eq!("8psk".parse(), Ok(PskOrder::Psk8));
+        assert!("16psk".parse::<PskOrder>().is_err());
+    }
+
+    #[test]
+    fn test_psk_differential() {
+        for order in [PskOrder::Bpsk, PskOrder::Qpsk, PskOrder::Psk8] {
+            let config = PskConfig {
+                order,
+                ..PskConfig::default()
+            };
+            let coherent = Psk::with_config(SAMPLE_RATE, config.clone());
+            let differential = Psk::with_config(
+                SAMPLE_RATE,
+                PskConfig {
+                    differential: true,
+                    ..config
+                },
+            );
+
+            let data = (0..differential.min_modulate_bytes())
+                .map(|_| rand::random::<u8>())
+                .collect::<Vec<_>>();
+
+            // An inverted polarity and a one sample late detector turn every
+            // symbol alike, only the transitions between them survive.
+            let distort = |psk: &Psk| {
+                let mut modulated = psk.modulate(&data);
+                modulated.rotate_left(1);
+                modulated.iter_mut().for_each(|sample| {
+                    *sample = -*sample + FP::from((rand::random::<f32>() - 0.5) * 0.25);
+                });
+                modulated
+            };
+
+            assert_ne!(
+                coherent.demodulate(&distort(&coherent)),
+                data,
+                "{:?}",
+                order
+            );
+
+            let modulated = distort(&differential);
+            assert_eq!(differential.demodulate(&modulated), data, "{:?}", order);
+            let llrs = differential.demodulate_soft(&modulated);
+            assert_eq!(BitByteConverter::llrs_to_bytes(&llrs), data, "{:?}", order);
+        }
+    }
 }
@@ -12,14 +12,22 @@ const SAMPLES_PER_PACKET: usize = (SAMPLE_REPEAT_TIMES * BITS_PER_PACKET) / 4 *
 pub struct BitWave;
 
 impl Modem for BitWave {
-    const MIN_MODULATE_BYTES: usize = BYTES_PER_PACKET;
-    const PREFERED_PAYLOAD_BYTES: usize = BYTES_PER_PACKET;
-    const PREAMBLE_FREQUENCY_RANGE: (f32, f32) = (900.0, 3000.0);
-
     fn new(_: usize) -> Self {
         Self
     }
 
+    fn min_modulate_bytes(&self) -> usize {
+        BYTES_PER_PACKET
+    }
+
+    fn prefered_payload_bytes(&self) -> usize {
+        BYTES_PER_PACKET
+    }
+
+    fn preamble_frequency_range(&self) -> (f32, f32) {
+        (900.0, 3000.0)
+    }
+
     fn modulate(&self, bytes: &[u8]) -> Vec<FP> {
         bytes
             .chunks(BYTES_PER_PACKET)
@@ -48,6 +56,37 @@ impl Modem for BitWave {
             })
             .collect::<Vec<_>>()
     }
+
+    fn demodulate_soft(&self, samples: &[FP]) -> Vec<f32> {
+        // Every sample is +a or -a plus noise, both estimated over the block.
+        let count = samples.len().max(1) as f32;
+        let amplitude = samples
+            .iter()
+            .map(|x| FP::into::<f32>(*x).abs())
+            .sum::<f32>()
+            / count;
+        let noise = samples
+            .iter()
+            .map(|x| (FP::into::<f32>(*x).abs() - amplitude).powi(2))
+            .sum::<f32>()
+            / count;
+        let scale = 2.0 * amplitude / noise.max(f32::MIN_POSITIVE);
+
+        samples
+            .chunks(SAMPLES_PER_PACKET)
+            .flat_map(|chunk| {
+                let code_llrs = chunk
+                    .chunks_exact(SAMPLE_REPEAT_TIMES)
+                    .map(|x| -scale * x.iter().map(|x| FP::into::<f32>(*x)).sum::<f32>())
+                    .collect::<Vec<_>>();
+
+                // Back to the LSB first order of the other modems, byte by byte.
+                let mut llrs = Self::decode_4b5b_soft(&Self::decode_nrzi_soft(&code_llrs));
+                llrs.chunks_mut(8).for_each(|byte| byte.reverse());
+                llrs
+            })
+            .collect()
+    }
 }
 
 impl BitWave {
@@ -77,6 +116,19 @@ impl BitWave {
             .collect()
     }
 
+    fn decode_nrzi_soft(llrs: &[f32]) -> Vec<f32> {
+        // A bit is the XOR of two line levels, its min-sum LLR is as sure as
+        // the weaker of them. The level before the packet is a known zero.
+        let mut current = f32::INFINITY;
+        llrs.iter()
+            .map(|llr| {
+                let result = llr.signum() * current.signum() * llr.abs().min(current.abs());
+                current = *llr;
+                result
+            })
+            .collect()
+    }
+
     fn encode_4b5b(bits: BitVecU8) -> BitVecU8 {
         assert!(bits.len() % 4 == 0);
         let mut out = BitVecU8::with_capacity(bits.len() / 4 * 5);
@@ -87,6 +139,34 @@ impl BitWave {
         out
     }
 
+    fn decode_4b5b_soft(llrs: &[f32]) -> Vec<f32> {
+        assert!(llrs.len().is_multiple_of(5));
+        llrs.chunks_exact(5)
+            .flat_map(|llrs| {
+                // Max-log over the sixteen valid code words, a group that is
+                // no code word at all still votes for its closest neighbours.
+                let metrics = Self::B5B_TABLE.map(|code| {
+                    llrs.iter()
+                        .enumerate()
+                        .map(|(index, llr)| match (code >> (4 - index)) & 1 {
+                            0 => llr / 2.0,
+                            _ => -llr / 2.0,
+                        })
+                        .sum::<f32>()
+                });
+
+                (0..4).map(move |index| {
+                    let mut nearest = [f32::MIN; 2];
+                    metrics.iter().enumerate().for_each(|(value, metric)| {
+                        let nearest = &mut nearest[(value >> (3 - index)) & 1];
+                        *nearest = nearest.max(*metric);
+                    });
+                    nearest[0] - nearest[1]
+                })
+            })
+            .collect()
+    }
+
     fn decode_4b5b(bits: BitVecU8) -> BitVecU8 {
         assert!(bits.len() % 5 == 0);
         let mut out = BitVecU8::with_capacity(bits.len() / 5 * 4);
@@ -104,6 +184,8 @@ impl BitWave {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::modem::tests::assert_erasure;
+    use crate::modem::BitByteConverter;
 
     const TEST_SEQUENCE_BYTES: usize = 100;
 
@@ -127,4 +209,26 @@ mod tests {
 
         assert_eq!(data, demodulated);
     }
+
+    #[test]
+    fn test_bitwave_soft() {
+        let data = (0..TEST_SEQUENCE_BYTES)
+            .map(|_| rand::random::<u8>())
+            .collect::<Vec<_>>();
+
+        let bitwave = BitWave::new(0);
+        let mut modulated = bitwave.modulate(&data);
+
+        modulated
+            .iter_mut()
+            .for_each(|sample| *sample += FP::from(rand::random::<f32>()) / FP::from(2.0));
+
+        let llrs = bitwave.demodulate_soft(&modulated);
+        assert_eq!(llrs.len(), data.len() * 8);
+        assert_eq!(BitByteConverter::llrs_to_bytes(&llrs), data);
+
+        // Silencing a whole 4B5B code word erases the high nibble of the first
+        // byte, the bits after the first byte are untouched.
+        assert_erasure(&bitwave, modulated, 0..SAMPLE_REPEAT_TIMES * 5, 4..8);
+    }
 }
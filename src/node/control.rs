@@ -0,0 +1,61 @@
+use crate::modem::BitLoading;
+
+const CONTROL_MAGIC_NUMBER: [u8; 6] = [0x19, 0x19, 0x81, 0x0b, 0x11, 0x45];
+
+const BIT_LOADING_REQUEST: u8 = 1;
+const BIT_LOADING_APPLIED: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlFrame {
+    // The receiver asks its peer to send with the allocation it measured.
+    BitLoadingRequest(BitLoading),
+    // Sent with the old allocation, the frames after it use the new one.
+    BitLoadingApplied(BitLoading),
+}
+
+impl ControlFrame {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (kind, bit_loading) = match self {
+            Self::BitLoadingRequest(bit_loading) => (BIT_LOADING_REQUEST, bit_loading),
+            Self::BitLoadingApplied(bit_loading) => (BIT_LOADING_APPLIED, bit_loading),
+        };
+
+        [&CONTROL_MAGIC_NUMBER[..], &[kind], &bit_loading.to_bytes()].concat()
+    }
+
+    pub fn from_bytes(frame: &[u8]) -> Option<Self> {
+        let payload = frame.strip_prefix(&CONTROL_MAGIC_NUMBER[..])?;
+        let (kind, bit_loading) = payload.split_first()?;
+        let bit_loading = BitLoading::from_bytes(bit_loading)?;
+
+        match *kind {
+            BIT_LOADING_REQUEST => Some(Self::BitLoadingRequest(bit_loading)),
+            BIT_LOADING_APPLIED => Some(Self::BitLoadingApplied(bit_loading)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_frame() {
+        let bit_loading = BitLoading::from_bytes(&[0, 1, 2, 4, 6]).unwrap();
+
+        for control in [
+            ControlFrame::BitLoadingRequest(bit_loading.clone()),
+            ControlFrame::BitLoadingApplied(bit_loading.clone()),
+        ] {
+            let bytes = control.to_bytes();
+            assert_eq!(ControlFrame::from_bytes(&bytes), Some(control));
+        }
+
+        // Frames from the interface pass through untouched.
+        assert_eq!(ControlFrame::from_bytes(&[0xff; 64]), None);
+        let mut unknown = ControlFrame::BitLoadingRequest(bit_loading).to_bytes();
+        unknown[CONTROL_MAGIC_NUMBER.len()] = 0;
+        assert_eq!(ControlFrame::from_bytes(&unknown), None);
+    }
+}
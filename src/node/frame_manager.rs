@@ -1,8 +1,10 @@
-use crate::modem::Modem;
+use crate::modem::{BitLoading, Modem};
 use std::marker::PhantomData;
 
 const MIN_VALID_FRAME_LENGTH: usize = 1;
 const FRAME_PREAMBLE: [u8; 4] = [0b10101010, 0b10101010, 0b10101010, 0b10101011];
+const MIN_PACKET_LENGTH: usize =
+    FRAME_PREAMBLE.len() + std::mem::size_of::<u16>() + MIN_VALID_FRAME_LENGTH;
 
 enum FrameManagerState {
     Waiting,
@@ -11,6 +13,7 @@ enum FrameManagerState {
 
 pub struct FrameManager<M> {
     phantom: PhantomData<M>,
+    packet_length: usize,
     buffer: Vec<u8>,
     frame_length: u16,
     current_state: FrameManagerState,
@@ -20,16 +23,21 @@ impl<M> FrameManager<M>
 where
     M: Modem + Sync + Send + 'static,
 {
-    pub fn new() -> Self {
+    pub fn new(modem: &M) -> Self {
         Self {
             phantom: PhantomData,
+            packet_length: modem.min_modulate_bytes(),
             buffer: Vec::new(),
             frame_length: 0,
             current_state: FrameManagerState::Waiting,
         }
     }
 
-    pub fn construct(frame: &[u8]) -> Vec<Vec<u8>> {
+    pub fn is_waiting(&self) -> bool {
+        matches!(self.current_state, FrameManagerState::Waiting)
+    }
+
+    pub fn construct(modem: &M, frame: &[u8]) -> Vec<Vec<u8>> {
         let mut result = [
             &FRAME_PREAMBLE[..],
             &u16::to_ne_bytes(frame.len() as u16),
@@ -37,20 +45,33 @@ where
         ]
         .concat();
 
-        let packet_length = <M as Modem>::MIN_MODULATE_BYTES;
+        let packet_length = modem.min_modulate_bytes();
         let packet_num = result.len().div_ceil(packet_length);
         result.resize(packet_num * packet_length, 0);
 
         result.chunks(packet_length).map(Vec::from).collect()
     }
 
+    pub fn check_bit_loading(modem: &M, bit_loading: &BitLoading) -> Result<(), String> {
+        // A sparse allocation can shrink the packets below the frame header.
+        let packet_length = modem.check_bit_loading(bit_loading)?;
+        if packet_length < MIN_PACKET_LENGTH {
+            return Err(format!(
+                "Bit loading leaves {} bytes per packet, a frame needs {}",
+                packet_length, MIN_PACKET_LENGTH
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn set_bit_loading(modem: &mut M, bit_loading: BitLoading) -> Result<(), String> {
+        Self::check_bit_loading(modem, &bit_loading)?;
+        modem.set_bit_loading(bit_loading)
+    }
+
     pub fn update(&mut self, packet: &[u8]) -> Option<Vec<u8>> {
-        assert!(
-            packet.len()
-                >= FRAME_PREAMBLE.len()
-                    + std::mem::size_of_val(&self.frame_length)
-                    + MIN_VALID_FRAME_LENGTH,
-        );
+        assert!(packet.len() >= MIN_PACKET_LENGTH);
 
         match self.current_state {
             FrameManagerState::Waiting => {
@@ -66,9 +87,7 @@ where
                 self.buffer.extend(packet);
                 self.frame_length = u16::from_ne_bytes(frame_length.try_into().unwrap());
 
-                if (self.frame_length as usize + FRAME_PREAMBLE.len())
-                    <= <M as Modem>::MIN_MODULATE_BYTES
-                {
+                if (self.frame_length as usize + FRAME_PREAMBLE.len()) <= self.packet_length {
                     let result = self.buffer[..self.frame_length as usize].to_vec();
                     self.buffer.clear();
 
@@ -98,22 +117,23 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::modem::{BitWave, Ofdm};
+    use crate::modem::{BitWave, Ofdm, OfdmConfig};
 
     const TEST_EXTEA_BYTES: usize = 37;
     const TEST_REDUCE_BYTES: usize = 17;
 
     #[test]
     fn test_frame_manager_inexact() {
-        let packet_length = <BitWave as Modem>::MIN_MODULATE_BYTES;
-        let mut frame_manager = FrameManager::<BitWave>::new();
+        let modem = BitWave::new(0);
+        let packet_length = modem.min_modulate_bytes();
+        let mut frame_manager = FrameManager::new(&modem);
 
         let origin = (0..packet_length * 20 + TEST_EXTEA_BYTES)
             .map(|index| index as u8)
             .collect::<Vec<_>>();
         info!("Origin length: {:?}", origin.len());
 
-        let packets = FrameManager::<BitWave>::construct(&origin);
+        let packets = FrameManager::construct(&modem, &origin);
 
         for packet in packets {
             if let Some(frame) = frame_manager.update(&packet) {
@@ -128,15 +148,16 @@ mod test {
 
     #[test]
     fn test_frame_manager_small_size() {
-        let packet_length = <Ofdm as Modem>::MIN_MODULATE_BYTES;
-        let mut frame_manager = FrameManager::<Ofdm>::new();
+        let modem = Ofdm::new(0);
+        let packet_length = modem.min_modulate_bytes();
+        let mut frame_manager = FrameManager::new(&modem);
 
         let origin = (0..packet_length - TEST_REDUCE_BYTES)
             .map(|index| index as u8 % 7)
             .collect::<Vec<_>>();
         info!("[Seq1] Origin length: {:?}", origin.len());
 
-        let packets = FrameManager::<Ofdm>::construct(&origin);
+        let packets = FrameManager::construct(&modem, &origin);
 
         for packet in packets {
             if let Some(frame) = frame_manager.update(&packet) {
@@ -150,7 +171,7 @@ mod test {
             .collect::<Vec<_>>();
         info!("[Seq2] Origin length: {:?}", origin.len());
 
-        let packets = FrameManager::<Ofdm>::construct(&origin);
+        let packets = FrameManager::construct(&modem, &origin);
 
         for packet in packets {
             if let Some(frame) = frame_manager.update(&packet) {
@@ -162,4 +183,25 @@ mod test {
 
         unreachable!("Must be able to get frame response!");
     }
+
+    #[test]
+    fn test_frame_manager_bit_loading() {
+        let mut modem = Ofdm::with_config(OfdmConfig::cable());
+        let uniform = modem.bit_loading().unwrap();
+
+        // One BPSK carrier gives three bytes per packet, short of the header.
+        let mut sparse = vec![0; uniform.carriers().len()];
+        sparse[0] = 1;
+        let sparse = BitLoading::from_bytes(&sparse).unwrap();
+        assert_eq!(modem.check_bit_loading(&sparse), Ok(3));
+        assert!(FrameManager::check_bit_loading(&modem, &sparse).is_err());
+        assert!(FrameManager::set_bit_loading(&mut modem, sparse).is_err());
+        assert_eq!(modem.bit_loading(), Some(uniform.clone()));
+
+        let dense = BitLoading::from_bytes(&vec![6; uniform.carriers().len()]).unwrap();
+        assert!(FrameManager::check_bit_loading(&modem, &dense).is_ok());
+        assert_eq!(modem.bit_loading(), Some(uniform));
+        assert!(FrameManager::set_bit_loading(&mut modem, dense.clone()).is_ok());
+        assert_eq!(modem.bit_loading(), Some(dense));
+    }
 }
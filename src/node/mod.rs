@@ -1,4 +1,7 @@
-use once_cell::sync::Lazy;
+use crate::modem::Modem;
+
+mod control;
+pub use control::ControlFrame;
 
 mod corrector;
 pub use corrector::ErrorCorrector;
@@ -7,18 +10,16 @@ mod frame_manager;
 pub use frame_manager::FrameManager;
 
 mod receiver;
-pub use receiver::{Receiver, AveragePower};
+pub use receiver::{AveragePower, FrameTimestamp, Receiver};
 
 mod sender;
 pub use sender::Sender;
 
-static WARMUP_SEQUENCE: Lazy<Vec<u8>> = Lazy::new(|| {
-    #[cfg(feature = "cable_link")]
-    const WARMUP_SEQUENCE_BYTES: usize = 0;
-    #[cfg(not(feature = "cable_link"))]
-    const WARMUP_SEQUENCE_BYTES: usize = 24;
+mod offline;
+pub use offline::{OfflineReceiver, OfflineSender};
 
-    (0..WARMUP_SEQUENCE_BYTES)
-        .map(|_| rand::random::<u8>())
-        .collect()
-});
+fn warmup_sequence(modem: &impl Modem, warmup_bytes: usize) -> Vec<u8> {
+    // Rounded up to whole packets, which is all some modems can modulate.
+    let warmup_bytes = warmup_bytes.next_multiple_of(modem.min_modulate_bytes());
+    (0..warmup_bytes).map(|_| rand::random::<u8>()).collect()
+}
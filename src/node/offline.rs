@@ -0,0 +1,139 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::{warmup_sequence, FrameManager, FrameTimestamp, Receiver};
+use crate::audio::{AudioClock, AudioPacket};
+use crate::modem::{LinkProfile, Modem};
+use crate::number::FP;
+use crate::packet::{AgcConfig, AutomaticGain, PacketDetector};
+use crate::packet::{DetectorConfig, PreambleSequence};
+
+pub struct OfflineSender<M> {
+    modem: M,
+    preamble: Vec<FP>,
+    output: AudioPacket,
+    warmup: Mutex<Vec<FP>>,
+}
+
+impl<M> OfflineSender<M>
+where
+    M: Modem + Sync + Send + 'static,
+{
+    pub fn new(file: impl AsRef<Path>, sample_rate: usize) -> Self {
+        let modem = <M as Modem>::new(sample_rate);
+        let preamble_length = DetectorConfig::default().preamble_length;
+        Self::with_modem(file, sample_rate, modem, preamble_length)
+            .with_warmup(LinkProfile::default().warmup_bytes())
+    }
+
+    pub fn with_modem(
+        file: impl AsRef<Path>,
+        sample_rate: usize,
+        modem: M,
+        preamble_length: usize,
+    ) -> Self {
+        let preamble = PreambleSequence::new(&modem, sample_rate, preamble_length);
+        let output = AudioPacket::create_writer(file, sample_rate as u32);
+
+        output.write_chunk(&vec![0.0; preamble_length]);
+
+        Self {
+            modem,
+            preamble,
+            output,
+            warmup: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn with_warmup(mut self, warmup_bytes: usize) -> Self {
+        let warmup = warmup_sequence(&self.modem, warmup_bytes);
+        *self.warmup.get_mut().unwrap() = self.modem.modulate(&warmup);
+        self
+    }
+
+    pub fn send(&self, frame: &[u8]) {
+        let packets = FrameManager::construct(&self.modem, frame);
+
+        // Written ahead of the first frame, like the live sender does.
+        let warmup = std::mem::take(&mut *self.warmup.lock().unwrap());
+        warmup.iter().for_each(|&sample| {
+            self.output.write_sample(FP::into(sample));
+        });
+
+        packets.iter().for_each(|packet| {
+            self.preamble
+                .iter()
+                .chain(self.modem.modulate(packet).iter())
+                .for_each(|&sample| {
+                    self.output.write_sample(FP::into(sample));
+                });
+        });
+    }
+}
+
+pub struct OfflineReceiver<M> {
+    modem: M,
+    samples: std::vec::IntoIter<f32>,
+    agc: AutomaticGain,
+    packet_detector: PacketDetector,
+    frame_manager: FrameManager<M>,
+}
+
+impl<M> OfflineReceiver<M>
+where
+    M: Modem + Sync + Send + 'static,
+{
+    pub fn new(file: impl AsRef<Path>) -> Self {
+        let input = AudioPacket::create_reader(file);
+        let sample_rate = input.sample_rate().unwrap() as usize;
+        let modem = <M as Modem>::new(sample_rate);
+        Self::with_input(input, modem, DetectorConfig::default())
+    }
+
+    pub fn with_config(file: impl AsRef<Path>, modem: M, detector_config: DetectorConfig) -> Self {
+        Self::with_input(AudioPacket::create_reader(file), modem, detector_config)
+    }
+
+    fn with_input(input: AudioPacket, modem: M, detector_config: DetectorConfig) -> Self {
+        let sample_rate = input.sample_rate().unwrap() as usize;
+        let packet_detector =
+            Receiver::create_packet_detector(&modem, sample_rate, detector_config);
+        let frame_manager = FrameManager::new(&modem);
+
+        Self {
+            modem,
+            samples: input.read_all().into_iter(),
+            agc: AutomaticGain::new(sample_rate, AgcConfig::default()),
+            packet_detector,
+            frame_manager,
+        }
+    }
+
+    pub fn recv(&mut self) -> Option<Vec<u8>> {
+        self.recv_with_timestamp().map(|(frame, _)| frame)
+    }
+
+    pub fn recv_with_timestamp(&mut self) -> Option<(Vec<u8>, FrameTimestamp)> {
+        let mut frame_peak = 0;
+
+        for sample in self.samples.by_ref() {
+            let sample = self.agc.process(sample, !self.packet_detector.is_waiting());
+
+            if let Some(packet) = self.packet_detector.update(sample) {
+                let packet = self.modem.demodulate(packet);
+
+                if self.frame_manager.is_waiting() {
+                    frame_peak = self.packet_detector.peak_index();
+                }
+
+                if let Some(frame) = self.frame_manager.update(&packet) {
+                    debug!("Frame received: {:?}", frame);
+                    let timestamp = FrameTimestamp::new(AudioClock::default(), frame_peak);
+                    return Some((frame, timestamp));
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -1,119 +1,384 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 
-use crossbeam_channel::{unbounded, Receiver as ChannelReceiver};
-use jack::ProcessScope;
+use crossbeam_channel::{bounded, Receiver as ChannelReceiver, Sender as ChannelSender};
 
-use crate::audio::{Audio, AudioPorts};
-use crate::modem::Modem;
-use crate::number::FP;
-use crate::packet::{PacketDetector, PreambleSequence};
+use crate::audio::{sample_ring, Resampler, RingConsumer, RingStats};
+use crate::audio::{AudioBackend, AudioClock, AudioPorts, CallbackHandle, ClipCounter};
+use crate::modem::{BitLoading, Modem};
+use crate::packet::{AgcConfig, AutomaticGain, DetectorConfig, PacketDetector, PreambleSequence};
 
 use super::FrameManager;
 
+const CAPTURE_BUFFER_SECONDS: usize = 8;
+const CLOCK_ANCHORS: usize = 64;
+
 #[derive(Clone)]
-pub struct AveragePower(Arc<Mutex<f32>>);
+pub struct AveragePower(Arc<AtomicU32>);
 
 impl AveragePower {
     const REFRESH_FACTOR: f32 = 0.85;
     const COLLISION_THRESHOLD: f32 = 2.5e-4;
 
     fn new() -> Self {
-        Self(Arc::new(Mutex::new(1.0)))
+        Self(Arc::new(AtomicU32::new(1.0f32.to_bits())))
+    }
+
+    fn update(&self, samples: &[f32]) {
+        let average_power = samples.iter().fold(self.value(), |average_power, sample| {
+            average_power * (1.0 - Self::REFRESH_FACTOR) + sample.powi(2) * Self::REFRESH_FACTOR
+        });
+        self.0.store(average_power.to_bits(), Ordering::Relaxed);
     }
 
-    fn update(&self, sample: f32) {
-        let mut average_power = self.0.lock().unwrap();
-        *average_power *= 1.0 - Self::REFRESH_FACTOR;
-        *average_power += sample.powi(2) * Self::REFRESH_FACTOR;
+    fn value(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
     }
 
     pub fn colliding(&self) -> bool {
-        let average_power = self.0.lock().unwrap();
-        *average_power > Self::COLLISION_THRESHOLD
+        self.value() > Self::COLLISION_THRESHOLD
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTimestamp {
+    pub sample_index: usize,
+    pub frame_time: Option<u32>,
+}
+
+impl FrameTimestamp {
+    pub(super) fn new(clock: AudioClock, offset: usize) -> Self {
+        Self {
+            sample_index: clock.timetick + offset,
+            frame_time: clock
+                .frame_time
+                .map(|frame_time| frame_time.wrapping_add(offset as u32)),
+        }
+    }
+}
+
+struct CaptureClock {
+    anchors: ChannelSender<(usize, AudioClock)>,
+    written: usize,
+    skew: Option<usize>,
+}
+
+impl CaptureClock {
+    fn record(&mut self, clock: AudioClock, written: usize) {
+        // Counting ring positions stays exact until the ring drops samples or
+        // the device skips some, each such jump is sent along as an anchor.
+        let skew = clock.timetick.wrapping_sub(self.written);
+        if written > 0
+            && self.skew != Some(skew)
+            && self.anchors.try_send((self.written, clock)).is_ok()
+        {
+            self.skew = Some(skew);
+        }
+        self.written += written;
+    }
+}
+
+struct CaptureStream {
+    consumer: RingConsumer,
+    resampler: Option<Resampler>,
+    pending: VecDeque<f32>,
+    output: Vec<f32>,
+    anchors: ChannelReceiver<(usize, AudioClock)>,
+    clocks: VecDeque<(usize, AudioClock)>,
+}
+
+impl CaptureStream {
+    fn next_sample(&mut self) -> f32 {
+        let resampler = match self.resampler.as_mut() {
+            Some(resampler) => resampler,
+            None => {
+                let mut sample = 0.0;
+                self.consumer
+                    .read_blocking(std::slice::from_mut(&mut sample));
+                return sample;
+            }
+        };
+
+        while self.pending.is_empty() {
+            let mut sample = 0.0;
+            self.consumer
+                .read_blocking(std::slice::from_mut(&mut sample));
+
+            resampler.process(&[sample], &mut self.output);
+            self.pending.extend(self.output.drain(..));
+        }
+
+        self.pending.pop_front().unwrap()
+    }
+
+    fn timestamp(&mut self, position: usize) -> FrameTimestamp {
+        self.clocks.extend(self.anchors.try_iter());
+        while self.clocks.len() > CLOCK_ANCHORS {
+            self.clocks.pop_front();
+        }
+
+        let (anchor, clock) = self
+            .clocks
+            .iter()
+            .rev()
+            .find(|(anchor, _)| *anchor <= position)
+            .or(self.clocks.front())
+            .copied()
+            .unwrap_or_default();
+        FrameTimestamp::new(clock, position.saturating_sub(anchor))
+    }
+
+    fn device_offset(&self, modem_offset: usize) -> usize {
+        self.resampler.as_ref().map_or(modem_offset, |resampler| {
+            (modem_offset as f64 * resampler.ratio()).round() as usize
+        })
     }
 }
 
 pub struct Receiver<M> {
     pub average_power: AveragePower,
     pub recorded_data: Arc<Mutex<Vec<f32>>>,
-    modem: M,
-    sample_receiver: ChannelReceiver<f32>,
+    modem: Mutex<M>,
+    capture_stream: Mutex<CaptureStream>,
+    agc: Mutex<AutomaticGain>,
+    capture_gain: AtomicU32,
+    clip_counter: ClipCounter,
+    channel_snr: Mutex<Option<Vec<f32>>>,
     packet_detector: Arc<Mutex<PacketDetector>>,
     frame_manager: Arc<Mutex<FrameManager<M>>>,
+    ring_stats: RingStats,
+    callback_handle: CallbackHandle,
+    _audio: Arc<dyn AudioBackend>,
 }
 
 impl<M> Receiver<M>
 where
     M: Modem + Sync + Send + 'static,
 {
-    pub fn new(audio: &'static Audio) -> Self {
+    pub fn new(audio: Arc<dyn AudioBackend>) -> Self {
+        let modem_rate = audio.sample_rate();
+        Self::with_modem_rate(audio, modem_rate)
+    }
+
+    pub fn with_modem_rate(audio: Arc<dyn AudioBackend>, modem_rate: usize) -> Self {
+        let modem = <M as Modem>::new(modem_rate);
+        Self::with_modem(audio, modem_rate, modem, DetectorConfig::default())
+    }
+
+    pub fn with_modem(
+        audio: Arc<dyn AudioBackend>,
+        modem_rate: usize,
+        modem: M,
+        detector_config: DetectorConfig,
+    ) -> Self {
         let average_power = AveragePower::new();
-        let (sample_sender, sample_receiver) = unbounded();
+        let device_rate = audio.sample_rate();
+        let capacity = device_rate * CAPTURE_BUFFER_SECONDS;
+        let (mut sample_producer, sample_consumer) = sample_ring(capacity);
+        let ring_stats = sample_consumer.stats();
 
+        let (anchor_sender, anchor_receiver) = bounded(CLOCK_ANCHORS);
+        let mut capture_clock = CaptureClock {
+            anchors: anchor_sender,
+            written: 0,
+            skew: None,
+        };
+
+        let clip_counter = ClipCounter::new();
+        let clip_counter_clone = clip_counter.clone();
         let average_power_clone = average_power.clone();
-        let capture_callback = move |ports: &mut AudioPorts, ps: &ProcessScope| {
-            ports.capture.as_slice(&ps).iter().for_each(|&sample| {
-                average_power_clone.update(sample);
-                sample_sender.send(sample).unwrap();
-            });
+        let capture_callback = move |ports: &mut AudioPorts| {
+            average_power_clone.update(ports.capture);
+            clip_counter_clone.update(ports.capture);
+            let written = sample_producer.write(ports.capture);
+            capture_clock.record(ports.clock, written);
         };
 
-        audio.register(Box::new(capture_callback));
+        let callback_handle = audio.register(Box::new(capture_callback));
         info!("Capture demodulated data registered!");
 
         let recorded_data = Arc::new(Mutex::new(Vec::new()));
-        let frame_manager = Arc::new(Mutex::new(FrameManager::<M>::new()));
-        let (modem, packet_detector) = Self::create_packet_detector(audio);
+        let frame_manager = Arc::new(Mutex::new(FrameManager::new(&modem)));
+        let packet_detector = Self::create_packet_detector(&modem, modem_rate, detector_config);
+        let packet_detector = Arc::new(Mutex::new(packet_detector));
+
+        let capture_stream = CaptureStream {
+            consumer: sample_consumer,
+            resampler: (device_rate != modem_rate).then(|| Resampler::new(device_rate, modem_rate)),
+            pending: VecDeque::new(),
+            output: Vec::new(),
+            anchors: anchor_receiver,
+            clocks: VecDeque::new(),
+        };
 
         Self {
-            modem,
-            sample_receiver,
+            modem: Mutex::new(modem),
+            capture_stream: Mutex::new(capture_stream),
+            agc: Mutex::new(AutomaticGain::new(modem_rate, AgcConfig::default())),
+            capture_gain: AtomicU32::new(1.0f32.to_bits()),
+            clip_counter,
+            channel_snr: Mutex::new(None),
             packet_detector,
             recorded_data,
             average_power,
             frame_manager,
+            ring_stats,
+            callback_handle,
+            _audio: audio,
         }
     }
 
+    pub fn with_agc(mut self, config: AgcConfig) -> Self {
+        self.agc.get_mut().unwrap().configure(config);
+        self
+    }
+
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.capture_gain.load(Ordering::Relaxed))
+    }
+
     pub fn recv(&self) -> Vec<u8> {
+        self.recv_with_timestamp().0
+    }
+
+    pub fn recv_with_timestamp(&self) -> (Vec<u8>, FrameTimestamp) {
+        let mut capture_stream = self.capture_stream.lock().unwrap();
+        let mut agc = self.agc.lock().unwrap();
+        let mut frame_peak = 0;
+
         loop {
-            let sample = self.sample_receiver.recv().unwrap();
+            let sample = capture_stream.next_sample();
             self.recorded_data.lock().unwrap().push(sample);
 
-            if let Some(packet) = self
-                .packet_detector
-                .lock()
-                .unwrap()
-                .update(FP::from(sample))
-            {
-                if let Some(frame) = self
-                    .frame_manager
-                    .lock()
-                    .unwrap()
-                    .update(&self.modem.demodulate(&packet))
-                {
-                    debug!("Frame received: {:?}", frame);
-                    return frame;
+            // The gain is frozen while a packet is read so that the payload
+            // keeps the level of the preamble it was detected with.
+            let mut packet_detector = self.packet_detector.lock().unwrap();
+            let sample = agc.process(sample, !packet_detector.is_waiting());
+            self.capture_gain
+                .store(agc.gain().to_bits(), Ordering::Relaxed);
+
+            let packet = match packet_detector.update(sample) {
+                Some(packet) => {
+                    let modem = self.modem.lock().unwrap();
+                    if let Some(snr) = modem.channel_snr(packet) {
+                        *self.channel_snr.lock().unwrap() = Some(snr);
+                    }
+                    modem.demodulate(packet)
                 }
+                None => continue,
+            };
+
+            let mut frame_manager = self.frame_manager.lock().unwrap();
+            if frame_manager.is_waiting() {
+                frame_peak = packet_detector.peak_index();
+            }
+
+            if let Some(frame) = frame_manager.update(&packet) {
+                debug!("Frame received: {:?}", frame);
+                let position = capture_stream.device_offset(frame_peak);
+                return (frame, capture_stream.timestamp(position));
             }
         }
     }
 
-    fn create_packet_detector(audio: &'static Audio) -> (M, Arc<Mutex<PacketDetector>>) {
-        let sample_rate = audio.sample_rate.get().unwrap();
-        let modem = <M as Modem>::new(sample_rate);
+    pub fn overruns(&self) -> usize {
+        self.ring_stats.overruns()
+    }
 
-        let packet_detector = {
-            let payload_capacity = {
-                let payload_bytes = M::MIN_MODULATE_BYTES;
-                let empty_packet = modem.modulate(&vec![0; payload_bytes]);
-                empty_packet.len()
-            };
-            let preamble = PreambleSequence::<M>::new(sample_rate);
-            let packet_detector = PacketDetector::new(preamble, payload_capacity);
-            Arc::new(Mutex::new(packet_detector))
+    pub fn clipped_samples(&self) -> usize {
+        self.clip_counter.count()
+    }
+
+    pub fn channel_snr(&self) -> Option<Vec<f32>> {
+        self.channel_snr.lock().unwrap().clone()
+    }
+
+    pub fn bit_loading(&self) -> Option<BitLoading> {
+        self.modem.lock().unwrap().bit_loading()
+    }
+
+    pub fn check_bit_loading(&self, bit_loading: &BitLoading) -> Result<(), String> {
+        FrameManager::check_bit_loading(&*self.modem.lock().unwrap(), bit_loading)
+    }
+
+    pub fn set_bit_loading(&self, bit_loading: BitLoading) -> Result<(), String> {
+        // A frame half read with the old allocation is dropped, the packets
+        // keep their length so the detector carries on as it is.
+        let mut modem = self.modem.lock().unwrap();
+        FrameManager::set_bit_loading(&mut *modem, bit_loading)?;
+        *self.frame_manager.lock().unwrap() = FrameManager::new(&modem);
+        Ok(())
+    }
+
+    pub(super) fn create_packet_detector(
+        modem: &M,
+        sample_rate: usize,
+        config: DetectorConfig,
+    ) -> PacketDetector {
+        let payload_capacity = {
+            let payload_bytes = modem.min_modulate_bytes();
+            let empty_packet = modem.modulate(&vec![0; payload_bytes]);
+            empty_packet.len()
+        };
+        let preamble = PreambleSequence::new(modem, sample_rate, config.preamble_length);
+        PacketDetector::with_config(preamble, payload_capacity, config)
+    }
+}
+
+impl<M> Drop for Receiver<M> {
+    fn drop(&mut self) {
+        self.callback_handle.remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(timetick: usize) -> AudioClock {
+        AudioClock {
+            timetick,
+            frame_time: Some(timetick as u32),
+        }
+    }
+
+    #[test]
+    fn test_capture_clock_overrun() {
+        let (mut producer, consumer) = sample_ring(8);
+        let (anchor_sender, anchor_receiver) = bounded(CLOCK_ANCHORS);
+        let mut capture_clock = CaptureClock {
+            anchors: anchor_sender,
+            written: 0,
+            skew: None,
+        };
+        let mut capture_stream = CaptureStream {
+            consumer,
+            resampler: None,
+            pending: VecDeque::new(),
+            output: Vec::new(),
+            anchors: anchor_receiver,
+            clocks: VecDeque::new(),
         };
 
-        (modem, packet_detector)
+        // Blocks of four arrive while nobody reads, the third and fourth no
+        // longer fit and the device clock runs ahead of the ring.
+        for block in 0..4 {
+            let written = producer.write(&[block as f32; 4]);
+            capture_clock.record(clock(100 + block * 4), written);
+        }
+        (0..8).for_each(|_| {
+            capture_stream.next_sample();
+        });
+        let written = producer.write(&[4.0; 4]);
+        capture_clock.record(clock(116), written);
+
+        let before = capture_stream.timestamp(5);
+        assert_eq!(before.sample_index, 105);
+        assert_eq!(before.frame_time, Some(105));
+
+        let after = capture_stream.timestamp(9);
+        assert_eq!(after.sample_index, 117);
+        assert_eq!(after.frame_time, Some(117));
     }
 }
@@ -1,59 +1,174 @@
-use crossbeam_channel::{unbounded, Sender as ChannelSender};
-use jack::ProcessScope;
+use std::sync::{Arc, Mutex};
 
-use super::{FrameManager, WARMUP_SEQUENCE};
-use crate::audio::{Audio, AudioPorts};
-use crate::modem::Modem;
+use super::{warmup_sequence, FrameManager};
+use crate::audio::{sample_ring, Resampler, RingProducer, RingStats};
+use crate::audio::{AudioBackend, AudioPorts, CallbackHandle, ClipCounter, OutputLevel};
+use crate::modem::{BitLoading, LinkProfile, Modem};
 use crate::number::FP;
-use crate::packet::PreambleSequence;
+use crate::packet::{DetectorConfig, PreambleSequence};
+
+const PLAYBACK_BUFFER_SECONDS: usize = 8;
+
+struct PlaybackStream {
+    producer: RingProducer,
+    resampler: Option<Resampler>,
+    warmup: Vec<f32>,
+}
+
+impl PlaybackStream {
+    fn write(&mut self, samples: &[f32]) {
+        match self.resampler.as_mut() {
+            Some(resampler) => {
+                let mut output = Vec::new();
+                resampler.process(samples, &mut output);
+                resampler.flush(&mut output);
+                self.producer.write_blocking(&output);
+            }
+            None => self.producer.write_blocking(samples),
+        }
+    }
+}
 
 pub struct Sender<M> {
-    modem: M,
+    modem: Mutex<M>,
     preamble: Vec<FP>,
-    sample_sender: ChannelSender<f32>,
+    playback_stream: Mutex<PlaybackStream>,
+    output_level: OutputLevel,
+    clip_counter: ClipCounter,
+    ring_stats: RingStats,
+    callback_handle: CallbackHandle,
+    _audio: Arc<dyn AudioBackend>,
 }
 
 impl<M> Sender<M>
 where
     M: Modem + Sync + Send + 'static,
 {
-    pub fn new(audio: &'static Audio) -> Self {
-        let (sample_sender, sample_receiver) = unbounded();
+    pub fn new(audio: Arc<dyn AudioBackend>) -> Self {
+        let modem_rate = audio.sample_rate();
+        Self::with_modem_rate(audio, modem_rate)
+    }
 
-        let sample_rate = audio.sample_rate.get().unwrap();
-        let modem = <M as Modem>::new(sample_rate);
-        let preamble = PreambleSequence::<M>::new(sample_rate);
+    pub fn with_modem_rate(audio: Arc<dyn AudioBackend>, modem_rate: usize) -> Self {
+        let modem = <M as Modem>::new(modem_rate);
+        let preamble_length = DetectorConfig::default().preamble_length;
+        Self::with_modem(audio, modem_rate, modem, preamble_length)
+            .with_warmup(LinkProfile::default().warmup_bytes())
+    }
 
-        let playback_callback = move |ports: &mut AudioPorts, ps: &ProcessScope| {
-            for sample in ports.playback.as_mut_slice(&ps) {
-                *sample = sample_receiver.try_recv().unwrap_or(0.0)
-            }
+    pub fn with_modem(
+        audio: Arc<dyn AudioBackend>,
+        modem_rate: usize,
+        modem: M,
+        preamble_length: usize,
+    ) -> Self {
+        let device_rate = audio.sample_rate();
+        let capacity = device_rate * PLAYBACK_BUFFER_SECONDS;
+        let (sample_producer, mut sample_consumer) = sample_ring(capacity);
+        let ring_stats = sample_producer.stats();
+
+        let preamble = PreambleSequence::new(&modem, modem_rate, preamble_length);
+
+        let clip_counter = ClipCounter::new();
+        let clip_counter_clone = clip_counter.clone();
+        let playback_callback = move |ports: &mut AudioPorts| {
+            sample_consumer.read(ports.playback);
+            clip_counter_clone.update(ports.playback);
         };
 
-        audio.register(Box::new(playback_callback));
+        let callback_handle = audio.register(Box::new(playback_callback));
         info!("Playback modulated data registered!");
 
-        modem.modulate(&WARMUP_SEQUENCE).iter().for_each(|&sample| {
-            sample_sender.send(FP::into(sample)).unwrap();
-        });
+        let playback_stream = PlaybackStream {
+            producer: sample_producer,
+            resampler: (device_rate != modem_rate).then(|| Resampler::new(modem_rate, device_rate)),
+            warmup: Vec::new(),
+        };
 
         Self {
-            modem,
+            modem: Mutex::new(modem),
             preamble,
-            sample_sender,
+            playback_stream: Mutex::new(playback_stream),
+            output_level: OutputLevel::default(),
+            clip_counter,
+            ring_stats,
+            callback_handle,
+            _audio: audio,
         }
     }
 
+    pub fn with_output_level(mut self, output_level: OutputLevel) -> Self {
+        self.output_level = output_level;
+        self
+    }
+
+    pub fn with_warmup(mut self, warmup_bytes: usize) -> Self {
+        let warmup = Self::modulate_warmup(self.modem.get_mut().unwrap(), warmup_bytes);
+        self.playback_stream.get_mut().unwrap().warmup = warmup;
+        self
+    }
+
     pub fn send(&self, frame: &[u8]) {
-        let packets = FrameManager::<M>::construct(&frame);
-
-        packets.iter().for_each(|packet| {
-            self.preamble
-                .iter()
-                .chain(self.modem.modulate(&packet).iter())
-                .for_each(|&sample| {
-                    self.sample_sender.send(FP::into(sample)).unwrap();
-                });
-        });
+        // The modem stays locked until the frame is queued, a new bit loading
+        // then only applies to the frames queued after it.
+        let modem = self.modem.lock().unwrap();
+        let packets = FrameManager::construct(&*modem, frame);
+
+        let samples = packets
+            .iter()
+            .flat_map(|packet| {
+                let mut block = self
+                    .preamble
+                    .iter()
+                    .chain(modem.modulate(packet).iter())
+                    .map(|&sample| FP::into(sample))
+                    .collect::<Vec<_>>();
+
+                self.output_level.apply(&mut block);
+                block
+            })
+            .collect::<Vec<_>>();
+
+        // The warm-up waits for the first frame, by then the output level
+        // the caller settled on is known.
+        let mut playback_stream = self.playback_stream.lock().unwrap();
+        let mut warmup = std::mem::take(&mut playback_stream.warmup);
+        self.output_level.apply(&mut warmup);
+        warmup.extend(samples);
+        playback_stream.write(&warmup);
+    }
+
+    pub fn bit_loading(&self) -> Option<BitLoading> {
+        self.modem.lock().unwrap().bit_loading()
+    }
+
+    pub fn check_bit_loading(&self, bit_loading: &BitLoading) -> Result<(), String> {
+        FrameManager::check_bit_loading(&*self.modem.lock().unwrap(), bit_loading)
+    }
+
+    pub fn set_bit_loading(&self, bit_loading: BitLoading) -> Result<(), String> {
+        FrameManager::set_bit_loading(&mut *self.modem.lock().unwrap(), bit_loading)
+    }
+
+    pub fn underruns(&self) -> usize {
+        self.ring_stats.underruns()
+    }
+
+    pub fn clipped_samples(&self) -> usize {
+        self.clip_counter.count()
+    }
+
+    fn modulate_warmup(modem: &M, warmup_bytes: usize) -> Vec<f32> {
+        modem
+            .modulate(&warmup_sequence(modem, warmup_bytes))
+            .iter()
+            .map(|&sample| FP::into(sample))
+            .collect()
+    }
+}
+
+impl<M> Drop for Sender<M> {
+    fn drop(&mut self) {
+        self.callback_handle.remove();
     }
 }
@@ -0,0 +1,132 @@
+use crate::number::FP;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AgcConfig {
+    pub target_level: f32,
+    pub attack_seconds: f32,
+    pub release_seconds: f32,
+    pub min_gain: f32,
+    pub max_gain: f32,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        // Peaks are brought to full scale, the level the detector thresholds
+        // are tuned against. An instant attack never lets a sudden onset
+        // through at the gain of the silence before it.
+        Self {
+            target_level: 1.0,
+            attack_seconds: 0.0,
+            release_seconds: 0.5,
+            min_gain: 0.01,
+            max_gain: 1000.0,
+        }
+    }
+}
+
+impl AgcConfig {
+    pub fn disabled() -> Self {
+        Self {
+            min_gain: 1.0,
+            max_gain: 1.0,
+            ..Self::default()
+        }
+    }
+}
+
+pub struct AutomaticGain {
+    config: AgcConfig,
+    sample_rate: usize,
+    attack: f32,
+    release: f32,
+    envelope: f32,
+    gain: f32,
+}
+
+impl AutomaticGain {
+    pub fn new(sample_rate: usize, config: AgcConfig) -> Self {
+        let mut agc = Self {
+            config,
+            sample_rate,
+            attack: 0.0,
+            release: 0.0,
+            envelope: 0.0,
+            gain: 1.0,
+        };
+        agc.configure(config);
+        agc
+    }
+
+    pub fn configure(&mut self, config: AgcConfig) {
+        let coefficient = |seconds: f32| 1.0 - (-1.0 / (seconds * self.sample_rate as f32)).exp();
+
+        self.attack = coefficient(config.attack_seconds);
+        self.release = coefficient(config.release_seconds);
+        self.config = config;
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+        self.gain = self.config.max_gain;
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn process(&mut self, sample: f32, hold: bool) -> FP {
+        if !hold {
+            let level = sample.abs();
+            let rate = if level > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+            self.envelope += (level - self.envelope) * rate;
+
+            let gain = self.config.target_level / self.envelope;
+            self.gain = gain.clamp(self.config.min_gain, self.config.max_gain);
+        }
+
+        FP::from(sample * self.gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 48000;
+
+    fn settle(agc: &mut AutomaticGain, amplitude: f32) -> f32 {
+        (0..SAMPLE_RATE * 8).fold(0.0, |peak: f32, index| {
+            let phase = index as f32 / SAMPLE_RATE as f32 * 2.0 * std::f32::consts::PI * 1000.0;
+            let output: f32 = agc.process(phase.sin() * amplitude, false).into();
+            if index > SAMPLE_RATE * 7 {
+                peak.max(output.abs())
+            } else {
+                0.0
+            }
+        })
+    }
+
+    #[test]
+    fn test_agc() {
+        let mut agc = AutomaticGain::new(SAMPLE_RATE, AgcConfig::default());
+
+        for amplitude in [0.01, 0.2, 1.0, 4.0] {
+            let peak = settle(&mut agc, amplitude);
+            assert!((peak - 1.0).abs() < 0.1, "peak {} at {}", peak, amplitude);
+            assert!((agc.gain() * amplitude - 1.0).abs() < 0.1);
+            assert!(peak <= 1.0);
+        }
+
+        let gain = agc.gain();
+        agc.process(0.0, true);
+        assert_eq!(agc.gain(), gain);
+
+        agc.configure(AgcConfig::disabled());
+        assert!(settle(&mut agc, 0.01) <= 0.01);
+    }
+}
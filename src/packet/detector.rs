@@ -1,14 +1,48 @@
-use super::PREAMBLE_LENGTH;
+use crate::modem::LinkProfile;
 use crate::number::FP;
 use slice_deque::SliceDeque;
 
-cfg_if::cfg_if! {
-    if #[cfg(feature = "cable_link")] {
-        const DETECT_THRETSHOLD_MIN: f32 = 75.0;
-        const DETECT_THRETSHOLD_RATIO: f32 = 5.0;
-    } else {
-        const DETECT_THRETSHOLD_MIN: f32 = 20.0;
-        const DETECT_THRETSHOLD_RATIO: f32 = 5.0;
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectorConfig {
+    pub preamble_length: usize,
+    // Captured samples are levelled by the AGC first, so a preamble correlates
+    // to about half its length and only noise stays below 0.3 of it. Over the
+    // air the echoes share in the levelled peaks, which leaves the preamble
+    // nearer a third and the threshold at 0.15.
+    pub threshold_min: f32,
+    pub threshold_ratio: f32,
+}
+
+impl DetectorConfig {
+    pub fn cable() -> Self {
+        Self {
+            preamble_length: 240,
+            threshold_min: 72.0,
+            threshold_ratio: 5.0,
+        }
+    }
+
+    pub fn air() -> Self {
+        Self {
+            preamble_length: 480,
+            threshold_min: 72.0,
+            threshold_ratio: 5.0,
+        }
+    }
+}
+
+impl From<LinkProfile> for DetectorConfig {
+    fn from(profile: LinkProfile) -> Self {
+        match profile {
+            LinkProfile::Cable => Self::cable(),
+            LinkProfile::Air => Self::air(),
+        }
+    }
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        LinkProfile::default().into()
     }
 }
 
@@ -20,30 +54,62 @@ pub enum PacketDetectorState {
 }
 
 pub struct PacketDetector {
+    config: DetectorConfig,
     preamble: Vec<FP>,
     detect_buffer: SliceDeque<FP>,
     payload_buffer: Vec<FP>,
     current_state: PacketDetectorState,
     correlation_buffer: SliceDeque<FP>,
+    sample_count: usize,
+    peak_index: usize,
+    peak_correlation: FP,
 }
 
 impl PacketDetector {
     pub fn new(preamble: Vec<FP>, payload_capacity: usize) -> Self {
+        Self::with_config(preamble, payload_capacity, DetectorConfig::default())
+    }
+
+    pub fn with_config(preamble: Vec<FP>, payload_capacity: usize, config: DetectorConfig) -> Self {
+        let preamble_length = preamble.len();
+
         Self {
+            config,
             preamble,
-            detect_buffer: SliceDeque::with_capacity(PREAMBLE_LENGTH),
+            detect_buffer: SliceDeque::with_capacity(preamble_length),
             payload_buffer: Vec::with_capacity(payload_capacity),
             current_state: PacketDetectorState::Waiting,
-            correlation_buffer: SliceDeque::with_capacity(PREAMBLE_LENGTH),
+            correlation_buffer: SliceDeque::with_capacity(preamble_length),
+            sample_count: 0,
+            peak_index: 0,
+            peak_correlation: FP::ZERO,
         }
     }
 
+    pub fn is_waiting(&self) -> bool {
+        self.current_state == PacketDetectorState::Waiting
+    }
+
+    pub fn peak_index(&self) -> usize {
+        self.peak_index
+    }
+
     pub fn update(&mut self, sample: FP) -> Option<&Vec<FP>> {
-        if self.detect_buffer.len() == PREAMBLE_LENGTH {
+        let preamble_length = self.preamble.len();
+        let sample_index = self.sample_count;
+        self.sample_count += 1;
+
+        if self.detect_buffer.len() == preamble_length {
             self.detect_buffer.pop_front();
         }
         self.detect_buffer.push_back(sample);
 
+        // A partially filled window only correlates against the head of the
+        // preamble, its growth stalls in rounding and fakes an early peak.
+        if self.detect_buffer.len() < preamble_length {
+            return None;
+        }
+
         let get_correlation = || -> FP {
             self.detect_buffer
                 .iter()
@@ -53,9 +119,23 @@ impl PacketDetector {
         };
 
         if self.current_state == PacketDetectorState::MaybePayload {
-            if get_correlation() > *self.correlation_buffer.back().unwrap() {
-                self.current_state = PacketDetectorState::Waiting;
-            } else {
+            // The correlation ripples ahead of its main lobe, so a peak only
+            // counts once nothing higher follows for a sixteenth of the window.
+            // The lobe still feeds the average, the payload tail after it then
+            // faces the same bar as before.
+            let correlation = get_correlation();
+            if self.correlation_buffer.len() == preamble_length {
+                self.correlation_buffer.pop_front();
+            }
+            self.correlation_buffer.push_back(correlation);
+
+            if correlation > self.peak_correlation {
+                self.peak_correlation = correlation;
+                self.peak_index = sample_index;
+                self.payload_buffer.clear();
+                return None;
+            }
+            if sample_index - self.peak_index >= preamble_length / 16 {
                 self.current_state = PacketDetectorState::Payload;
             }
         }
@@ -64,25 +144,27 @@ impl PacketDetector {
             PacketDetectorState::Waiting => {
                 let correlation = get_correlation();
 
-                if self.correlation_buffer.len() == PREAMBLE_LENGTH {
+                if self.correlation_buffer.len() == preamble_length {
                     self.correlation_buffer.pop_front();
                 }
                 self.correlation_buffer.push_back(correlation);
 
                 let average_correlation =
                     self.correlation_buffer.iter().map(|&x| x.abs()).sum::<FP>()
-                        / FP::from(PREAMBLE_LENGTH);
+                        / FP::from(preamble_length);
 
-                if correlation > FP::from(DETECT_THRETSHOLD_MIN)
-                    && correlation > average_correlation * FP::from(DETECT_THRETSHOLD_RATIO)
+                if correlation > FP::from(self.config.threshold_min)
+                    && correlation > average_correlation * FP::from(self.config.threshold_ratio)
                 {
                     self.current_state = PacketDetectorState::MaybePayload;
                     self.payload_buffer.clear();
+                    self.peak_index = sample_index;
+                    self.peak_correlation = correlation;
                 }
 
                 None
             }
-            PacketDetectorState::Payload => {
+            PacketDetectorState::Payload | PacketDetectorState::MaybePayload => {
                 self.payload_buffer.push(sample);
 
                 if self.payload_buffer.len() == self.payload_buffer.capacity() {
@@ -92,7 +174,61 @@ impl PacketDetector {
 
                 None
             }
-            PacketDetectorState::MaybePayload => unreachable!(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modem::{Modem, Ofdm, OfdmConfig};
+    use crate::packet::PreambleSequence;
+
+    const SAMPLE_RATE: usize = 48000;
+
+    #[test]
+    fn test_detector_stream_start() {
+        // The stream opens right on the preamble, as a pipe does, so only the
+        // full window may lock and not the head of the preamble alone.
+        let config = DetectorConfig::default();
+        let modem = Ofdm::new(SAMPLE_RATE);
+        let preamble = PreambleSequence::new(&modem, SAMPLE_RATE, config.preamble_length);
+        let payload = (0..64)
+            .map(|index| FP::from(index as f32 / 64.0))
+            .collect::<Vec<_>>();
+
+        let mut detector = PacketDetector::with_config(preamble.clone(), payload.len(), config);
+        let detected = preamble
+            .iter()
+            .chain(payload.iter())
+            .filter_map(|sample| detector.update(*sample).cloned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(detected, [payload]);
+        assert_eq!(detector.peak_index(), preamble.len() - 1);
+    }
+
+    #[test]
+    fn test_detector_ripple() {
+        // The low air threshold lets the ripple ahead of the main lobe
+        // through, the lock has to wait for the lobe itself.
+        let config = DetectorConfig::air();
+        let modem = Ofdm::with_config(OfdmConfig::air());
+        let preamble = PreambleSequence::new(&modem, SAMPLE_RATE, config.preamble_length);
+        let silence = vec![FP::ZERO; config.preamble_length];
+        let payload = (0..64)
+            .map(|index| FP::from(index as f32 / 64.0))
+            .collect::<Vec<_>>();
+
+        let mut detector = PacketDetector::with_config(preamble.clone(), payload.len(), config);
+        let detected = silence
+            .iter()
+            .chain(preamble.iter())
+            .chain(payload.iter())
+            .filter_map(|sample| detector.update(*sample).cloned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(detected, [payload]);
+        assert_eq!(detector.peak_index(), silence.len() + preamble.len() - 1);
+    }
+}
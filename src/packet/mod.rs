@@ -1,5 +1,8 @@
+mod agc;
+pub use agc::{AgcConfig, AutomaticGain};
+
 mod detector;
-pub use detector::PacketDetector;
+pub use detector::{DetectorConfig, PacketDetector};
 
 mod preamble;
-pub use preamble::{PreambleSequence, PREAMBLE_LENGTH};
+pub use preamble::PreambleSequence;
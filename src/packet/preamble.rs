@@ -1,21 +1,13 @@
 use crate::{modem::Modem, number::FP};
-use std::marker::PhantomData;
 
-#[cfg(feature = "cable_link")]
-pub const PREAMBLE_LENGTH: usize = 240;
-#[cfg(not(feature = "cable_link"))]
-pub const PREAMBLE_LENGTH: usize = 480;
+pub struct PreambleSequence;
 
-pub struct PreambleSequence<M> {
-    modem: PhantomData<M>,
-}
-
-impl<M: Modem> PreambleSequence<M> {
-    pub fn new(sample_rate: usize) -> Vec<FP> {
-        let (freq_min, freq_max) = <M as Modem>::PREAMBLE_FREQUENCY_RANGE;
+impl PreambleSequence {
+    pub fn new(modem: &dyn Modem, sample_rate: usize, length: usize) -> Vec<FP> {
+        let (freq_min, freq_max) = modem.preamble_frequency_range();
 
         let frequency_diff = FP::from(freq_max) - FP::from(freq_min);
-        let preamble_center = FP::from(PREAMBLE_LENGTH) / FP::from(2.0);
+        let preamble_center = FP::from(length) / FP::from(2.0);
 
         let get_frequency = |index: usize| {
             if index < FP::into::<usize>(preamble_center) {
@@ -28,9 +20,9 @@ impl<M: Modem> PreambleSequence<M> {
         };
 
         let mut integral: FP = FP::ZERO;
-        let mut preamble_samples: Vec<FP> = Vec::with_capacity(PREAMBLE_LENGTH);
+        let mut preamble_samples: Vec<FP> = Vec::with_capacity(length);
 
-        for index in 0..PREAMBLE_LENGTH {
+        for index in 0..length {
             integral += get_frequency(index) / FP::from(sample_rate);
             preamble_samples.push((integral * FP::from(2.0) * FP::PI).sin());
         }
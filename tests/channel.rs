@@ -0,0 +1,254 @@
+use audio_network::audio::{ChannelProfile, ChannelSimulator};
+use audio_network::modem::{BitLoading, BitWave, Constellation, Fsk, FskConfig, Modem};
+use audio_network::modem::{Ofdm, OfdmConfig, Psk};
+use audio_network::number::FP;
+use audio_network::packet::PreambleSequence;
+use audio_network::packet::{AgcConfig, AutomaticGain, DetectorConfig, PacketDetector};
+
+const SAMPLE_RATE: usize = 48000;
+const CHUNK_SAMPLES: usize = 1024;
+const SILENCE_SAMPLES: usize = 2000;
+const TEST_PACKETS: usize = 8;
+
+fn transmit<M: Modem>(profile: ChannelProfile) -> usize {
+    transmit_with_agc::<M>(profile, AgcConfig::default())
+}
+
+fn transmit_with_agc<M: Modem>(profile: ChannelProfile, agc: AgcConfig) -> usize {
+    let modem = M::new(SAMPLE_RATE);
+    transmit_modem(&modem, DetectorConfig::default(), profile, agc)
+}
+
+fn transmit_modem(
+    modem: &dyn Modem,
+    detector_config: DetectorConfig,
+    profile: ChannelProfile,
+    agc: AgcConfig,
+) -> usize {
+    let (packets, payloads) = capture_modem(modem, detector_config, profile, agc);
+    let decoded = payloads
+        .iter()
+        .map(|payload| modem.demodulate(payload))
+        .collect::<Vec<_>>();
+
+    packets
+        .iter()
+        .filter(|packet| decoded.iter().any(|x| x[..packet.len()] == packet[..]))
+        .count()
+}
+
+fn capture_modem(
+    modem: &dyn Modem,
+    detector_config: DetectorConfig,
+    profile: ChannelProfile,
+    agc: AgcConfig,
+) -> (Vec<Vec<u8>>, Vec<Vec<FP>>) {
+    let preamble = PreambleSequence::new(modem, SAMPLE_RATE, detector_config.preamble_length);
+
+    let packets = (0..TEST_PACKETS)
+        .map(|seed| {
+            (0..modem.min_modulate_bytes())
+                .map(|index| (index * 31 + seed * 7) as u8)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut transmitted = vec![0.0; SILENCE_SAMPLES];
+    for packet in packets.iter() {
+        preamble
+            .iter()
+            .chain(modem.modulate(packet).iter())
+            .for_each(|&sample| transmitted.push(FP::into(sample)));
+        transmitted.extend(std::iter::repeat_n(0.0, SILENCE_SAMPLES));
+    }
+
+    let mut simulator = ChannelSimulator::new(profile);
+    let mut received = Vec::new();
+    transmitted
+        .chunks(CHUNK_SAMPLES)
+        .for_each(|chunk| simulator.process(chunk, &mut received));
+
+    let payload_capacity = modem.modulate(&vec![0; modem.min_modulate_bytes()]).len();
+    let mut detector =
+        PacketDetector::with_config(preamble.clone(), payload_capacity, detector_config);
+
+    let mut agc = AutomaticGain::new(SAMPLE_RATE, agc);
+
+    let mut payloads = Vec::new();
+    for &sample in received.iter() {
+        let sample = agc.process(sample, !detector.is_waiting());
+        if let Some(payload) = detector.update(sample) {
+            payloads.push(payload.to_vec());
+        }
+    }
+
+    (packets, payloads)
+}
+
+#[test]
+fn channel_ideal() {
+    assert_eq!(transmit::<Ofdm>(ChannelProfile::ideal()), TEST_PACKETS);
+    assert_eq!(transmit::<Psk>(ChannelProfile::ideal()), TEST_PACKETS);
+    assert_eq!(transmit::<BitWave>(ChannelProfile::ideal()), TEST_PACKETS);
+}
+
+#[test]
+fn channel_cable() {
+    assert_eq!(transmit::<Ofdm>(ChannelProfile::cable()), TEST_PACKETS);
+    assert_eq!(transmit::<Psk>(ChannelProfile::cable()), TEST_PACKETS);
+    assert_eq!(transmit::<BitWave>(ChannelProfile::cable()), TEST_PACKETS);
+}
+
+#[test]
+fn channel_attenuated_agc() {
+    let profile = ChannelProfile {
+        attenuation_db: 30.0,
+        ..ChannelProfile::cable()
+    };
+
+    assert_eq!(
+        transmit_with_agc::<Ofdm>(profile.clone(), AgcConfig::disabled()),
+        0
+    );
+    assert_eq!(transmit::<Ofdm>(profile.clone()), TEST_PACKETS);
+    assert_eq!(transmit::<Psk>(profile), TEST_PACKETS);
+}
+
+#[test]
+fn channel_air_preset() {
+    let modem = Ofdm::with_config(OfdmConfig::air());
+    let received = transmit_modem(
+        &modem,
+        DetectorConfig::air(),
+        ChannelProfile::cable(),
+        AgcConfig::default(),
+    );
+    assert_eq!(received, TEST_PACKETS);
+}
+
+#[test]
+fn channel_air_link() {
+    // Echoes and noise on top of the preamble set the peaks the AGC levels,
+    // so its correlation lands well short of half its length.
+    let modem = Ofdm::with_config(OfdmConfig::air());
+    let received = transmit_modem(
+        &modem,
+        DetectorConfig::air(),
+        ChannelProfile::air(),
+        AgcConfig::default(),
+    );
+    assert_eq!(received, TEST_PACKETS);
+}
+
+#[test]
+fn channel_bit_loading() {
+    // An echo two samples late notches the middle of the cable band, a short
+    // cyclic prefix keeps it from smearing into the next symbol.
+    let profile = ChannelProfile {
+        multipath: vec![(2, 0.9)],
+        ..ChannelProfile::cable()
+    };
+    let config = OfdmConfig {
+        cyclic_prefix_samples: 4,
+        ..OfdmConfig::cable()
+    };
+    // Data carriers are counted from the band start, past the lower pilot.
+    let notch = config.data_samples / 4 - config.start_sub_carrier_index - 1;
+
+    let uniform = Ofdm::with_config(config.clone());
+    let (_, payloads) = capture_modem(
+        &uniform,
+        DetectorConfig::cable(),
+        profile.clone(),
+        AgcConfig::default(),
+    );
+
+    let mut snr = vec![0.0; config.sub_carrier_count - config.pilot_sub_carriers.len()];
+    for payload in payloads.iter() {
+        let estimate = uniform.channel_snr(payload).unwrap();
+        snr.iter_mut()
+            .zip(estimate)
+            .for_each(|(snr, estimate)| *snr += estimate / payloads.len() as f32);
+    }
+    println!("Subcarrier SNR: {:?}", snr);
+    assert!(snr[notch] + 10.0 < snr[0]);
+
+    // The receiver sends its allocation back to the sender as plain bytes.
+    let feedback = BitLoading::allocate(&snr, 1e-5, 3.0).to_bytes();
+    let bit_loading = BitLoading::from_bytes(&feedback).unwrap();
+    println!("Bit loading: {:?}", bit_loading);
+    assert!(bit_loading.carriers()[notch] < bit_loading.carriers()[0]);
+
+    let loaded = Ofdm::with_config(OfdmConfig {
+        bit_loading: Some(bit_loading),
+        ..config
+    });
+    let received = transmit_modem(
+        &loaded,
+        DetectorConfig::cable(),
+        profile.clone(),
+        AgcConfig::default(),
+    );
+    assert_eq!(received, TEST_PACKETS);
+    assert!(loaded.min_modulate_bytes() > uniform.min_modulate_bytes());
+
+    let received = transmit_modem(
+        &uniform,
+        DetectorConfig::cable(),
+        profile,
+        AgcConfig::default(),
+    );
+    assert!(received < TEST_PACKETS);
+}
+
+#[test]
+fn channel_pilot_tracking() {
+    // Eight times the usual packet length under a clock drift that walks the
+    // symbol timing by more than a sample before the packet ends.
+    let profile = ChannelProfile {
+        drift_ppm: 100.0,
+        ..ChannelProfile::cable()
+    };
+    let config = OfdmConfig {
+        constellation: Constellation::Qpsk,
+        cyclic_prefix_samples: 4,
+        data_symbol_per_packet: 192,
+        ..OfdmConfig::cable()
+    };
+
+    let tracked = Ofdm::with_config(config.clone());
+    let received = transmit_modem(
+        &tracked,
+        DetectorConfig::cable(),
+        profile.clone(),
+        AgcConfig::default(),
+    );
+    assert_eq!(received, TEST_PACKETS);
+
+    let untracked = Ofdm::with_config(OfdmConfig {
+        sub_carrier_count: config.sub_carrier_count - config.pilot_sub_carriers.len(),
+        pilot_sub_carriers: Vec::new(),
+        ..config
+    });
+    let received = transmit_modem(
+        &untracked,
+        DetectorConfig::cable(),
+        profile,
+        AgcConfig::default(),
+    );
+    assert_eq!(received, 0);
+}
+
+#[test]
+fn channel_fsk_reverb() {
+    // Echoes of a reverberant room that outlast a PSK symbol and the OFDM
+    // cyclic prefix, but die out within the FSK guard.
+    let profile = ChannelProfile {
+        multipath: vec![(17, 0.8), (41, 0.6), (73, -0.5)],
+        ..ChannelProfile::air()
+    };
+
+    let fsk = Fsk::with_config(SAMPLE_RATE, FskConfig::air());
+    let received = transmit_modem(&fsk, DetectorConfig::air(), profile, AgcConfig::default());
+    assert_eq!(received, TEST_PACKETS);
+}
@@ -0,0 +1,344 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use audio_network::audio::{AudioBackend, AudioDeactivateFlag, ChannelProfile, LoopbackAudio};
+use audio_network::audio::{AudioPacket, CreateCallback, OutputLevel};
+use audio_network::modem::ModemOptions;
+use audio_network::modem::{AnyModem, BitLoading, BitWave, Constellation};
+use audio_network::modem::{LinkProfile, Modem, ModemKind};
+use audio_network::modem::{Ofdm, OfdmConfig};
+use audio_network::node::{ControlFrame, FrameManager, Receiver, Sender};
+use audio_network::packet::DetectorConfig;
+
+const SAMPLE_RATE: usize = 48000;
+const BUFFER_SIZE: usize = 1024;
+const TEST_SEQUENCE_BYTES: usize = 300;
+
+#[test]
+fn loopback_ofdm() {
+    let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let frame_sander = Sender::<Ofdm>::new(audio.clone());
+    let frame_receiver = Receiver::<Ofdm>::new(audio.clone());
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+
+    assert_eq!(audio.buffer_size(), BUFFER_SIZE);
+    assert_eq!(frame_sander.underruns(), 0);
+    assert_eq!(frame_receiver.overruns(), 0);
+    assert!(frame_receiver.gain() > 1.0);
+    assert_eq!(frame_sander.clipped_samples(), 0);
+    assert_eq!(frame_receiver.clipped_samples(), 0);
+    assert!(frame_receiver.channel_snr().is_some());
+}
+
+#[test]
+fn loopback_bit_loading() {
+    let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let frame_sander = Sender::<Ofdm>::new(audio.clone());
+    let frame_receiver = Receiver::<Ofdm>::new(audio.clone());
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    assert_eq!(frame_receiver.recv(), test_data);
+
+    // The clean loopback carries 64-QAM everywhere, more than either preset.
+    let snr = frame_receiver.channel_snr().unwrap();
+    let bit_loading = BitLoading::allocate(&snr, 1e-5, 3.0);
+    assert_ne!(frame_receiver.bit_loading(), Some(bit_loading.clone()));
+
+    let request = ControlFrame::BitLoadingRequest(bit_loading.clone());
+    frame_sander.send(&request.to_bytes());
+    let feedback = ControlFrame::from_bytes(&frame_receiver.recv());
+    assert_eq!(feedback, Some(request));
+
+    // The confirmation is the last frame with the old allocation.
+    let applied = ControlFrame::BitLoadingApplied(bit_loading.clone());
+    frame_sander.send(&applied.to_bytes());
+    frame_sander.set_bit_loading(bit_loading.clone()).unwrap();
+    frame_sander.send(&test_data);
+
+    assert_eq!(
+        ControlFrame::from_bytes(&frame_receiver.recv()),
+        Some(applied)
+    );
+    frame_receiver.set_bit_loading(bit_loading.clone()).unwrap();
+    assert_eq!(frame_receiver.recv(), test_data);
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(frame_sander.bit_loading(), Some(bit_loading.clone()));
+    assert_eq!(frame_receiver.bit_loading(), Some(bit_loading));
+}
+
+#[test]
+fn loopback_any_modem() {
+    let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+    let detector_config = DetectorConfig::from(LinkProfile::Cable);
+    let options = ModemOptions::default();
+    let create_modem =
+        || AnyModem::with_kind(ModemKind::Psk, SAMPLE_RATE, LinkProfile::Cable, options).unwrap();
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES / 20)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let preamble_length = detector_config.preamble_length;
+    let frame_sander =
+        Sender::with_modem(audio.clone(), SAMPLE_RATE, create_modem(), preamble_length);
+    let frame_receiver =
+        Receiver::with_modem(audio.clone(), SAMPLE_RATE, create_modem(), detector_config);
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+}
+
+#[test]
+fn loopback_warmup() {
+    let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+    let detector_config = DetectorConfig::air();
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    // QPSK doubles the air packet past the warmup, which is padded to a
+    // whole one.
+    let preamble_length = detector_config.preamble_length;
+    let create_modem = || {
+        Ofdm::with_config(OfdmConfig {
+            constellation: Constellation::Qpsk,
+            ..OfdmConfig::air()
+        })
+    };
+    assert!(create_modem().min_modulate_bytes() > LinkProfile::Air.warmup_bytes());
+
+    let frame_sander =
+        Sender::with_modem(audio.clone(), SAMPLE_RATE, create_modem(), preamble_length)
+            .with_warmup(LinkProfile::Air.warmup_bytes());
+    let frame_receiver =
+        Receiver::with_modem(audio.clone(), SAMPLE_RATE, create_modem(), detector_config);
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+}
+
+#[test]
+fn loopback_clipping() {
+    let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let output_level = OutputLevel {
+        ceiling: f32::INFINITY,
+        ..OutputLevel::default()
+    };
+    // Only the sign of a BPSK carrier matters, so it still decodes with its
+    // peaks flattened, a QAM carrier would lose its amplitude levels.
+    let detector_config = DetectorConfig::default();
+    let create_modem = || {
+        Ofdm::with_config(OfdmConfig {
+            constellation: Constellation::Bpsk,
+            ..OfdmConfig::default()
+        })
+    };
+
+    let preamble_length = detector_config.preamble_length;
+    let frame_sander =
+        Sender::with_modem(audio.clone(), SAMPLE_RATE, create_modem(), preamble_length)
+            .with_output_level(output_level);
+    let frame_receiver =
+        Receiver::with_modem(audio.clone(), SAMPLE_RATE, create_modem(), detector_config);
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+    assert!(frame_sander.clipped_samples() > 0);
+    assert!(frame_receiver.clipped_samples() > 0);
+}
+
+#[test]
+fn loopback_bitwave() {
+    let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|_| rand::random::<u8>())
+        .collect();
+
+    let frame_sander = Sender::<BitWave>::new(audio.clone());
+    let frame_receiver = Receiver::<BitWave>::new(audio.clone());
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+}
+
+#[test]
+fn loopback_ofdm_impaired() {
+    let profile = ChannelProfile {
+        delay: 37,
+        attenuation_db: 1.0,
+        dc_offset: 0.005,
+        ..ChannelProfile::ideal()
+    };
+    let audio = LoopbackAudio::with_channel(SAMPLE_RATE, BUFFER_SIZE, profile);
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let frame_sander = Sender::<Ofdm>::new(audio.clone());
+    let frame_receiver = Receiver::<Ofdm>::new(audio.clone());
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+}
+
+#[test]
+fn loopback_callback_handle() {
+    let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+
+    let recorded = AudioPacket::create_buffer(SAMPLE_RATE);
+    let handle = audio.register(CreateCallback::capture(recorded.clone()));
+    audio.activate().unwrap();
+
+    std::thread::sleep(Duration::from_millis(100));
+    handle.pause();
+    std::thread::sleep(Duration::from_millis(50));
+
+    let paused_length = recorded.read_all().len();
+    assert!(paused_length > 0);
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(recorded.read_all().len(), paused_length);
+
+    handle.resume();
+    std::thread::sleep(Duration::from_millis(100));
+    handle.remove();
+    std::thread::sleep(Duration::from_millis(50));
+
+    let removed_length = recorded.read_all().len();
+    assert!(removed_length > paused_length);
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(recorded.read_all().len(), removed_length);
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+}
+
+#[test]
+fn loopback_lifecycle() {
+    let sentinel = Arc::new(());
+
+    for _ in 0..16 {
+        let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+        let weak_audio = Arc::downgrade(&audio);
+
+        let captured = sentinel.clone();
+        audio.register(Box::new(move |_| {
+            let _ = &captured;
+        }));
+
+        let frame_sander = Sender::<Ofdm>::new(audio.clone());
+        let frame_receiver = Receiver::<Ofdm>::new(audio.clone());
+        audio.activate().unwrap();
+        drop(audio);
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(weak_audio.upgrade().is_some());
+
+        drop(frame_sander);
+        drop(frame_receiver);
+
+        assert!(weak_audio.upgrade().is_none());
+        assert_eq!(Arc::strong_count(&sentinel), 1);
+    }
+}
+
+#[test]
+fn loopback_timestamp() {
+    let audio = LoopbackAudio::new(SAMPLE_RATE, BUFFER_SIZE);
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let frame_sander = Sender::<Ofdm>::new(audio.clone());
+    let frame_receiver = Receiver::<Ofdm>::new(audio.clone());
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    frame_sander.send(&test_data);
+    let (first_data, first_timestamp) = frame_receiver.recv_with_timestamp();
+    let (second_data, second_timestamp) = frame_receiver.recv_with_timestamp();
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(first_data, test_data);
+    assert_eq!(second_data, test_data);
+
+    let modem = Ofdm::new(SAMPLE_RATE);
+    let preamble_length = DetectorConfig::default().preamble_length;
+    let frame_samples: usize = FrameManager::construct(&modem, &test_data)
+        .iter()
+        .map(|packet| preamble_length + modem.modulate(packet).len())
+        .sum();
+
+    assert!(first_timestamp.sample_index >= preamble_length - 1);
+    assert_eq!(
+        second_timestamp.sample_index - first_timestamp.sample_index,
+        frame_samples
+    );
+    assert_eq!(first_timestamp.frame_time, None);
+}
+
+#[test]
+fn loopback_resampled() {
+    const DEVICE_SAMPLE_RATE: usize = 44100;
+
+    let audio = LoopbackAudio::new(DEVICE_SAMPLE_RATE, BUFFER_SIZE);
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let frame_sander = Sender::<Ofdm>::with_modem_rate(audio.clone(), SAMPLE_RATE);
+    let frame_receiver = Receiver::<Ofdm>::with_modem_rate(audio.clone(), SAMPLE_RATE);
+    audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+}
@@ -0,0 +1,112 @@
+use audio_network::audio::AudioPacket;
+use audio_network::modem::{AnyModem, BitWave, LinkProfile, Modem, ModemKind, ModemOptions, Ofdm};
+use audio_network::node::{FrameManager, OfflineReceiver, OfflineSender};
+use audio_network::packet::DetectorConfig;
+use temp_dir::TempDir;
+
+const SAMPLE_RATE: usize = 48000;
+const TEST_SEQUENCE_BYTES: usize = 300;
+const TEST_FRAMES: usize = 3;
+
+#[test]
+fn offline_ofdm() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.child("ofdm.wav");
+
+    let frames: Vec<Vec<u8>> = (0..TEST_FRAMES)
+        .map(|frame| {
+            (0..TEST_SEQUENCE_BYTES)
+                .map(|index| ((index + frame) % 256) as u8)
+                .collect()
+        })
+        .collect();
+
+    let frame_sender = OfflineSender::<Ofdm>::new(&file_path, SAMPLE_RATE);
+    frames.iter().for_each(|frame| frame_sender.send(frame));
+    drop(frame_sender);
+
+    let mut frame_receiver = OfflineReceiver::<Ofdm>::new(&file_path);
+    for frame in frames.iter() {
+        assert_eq!(frame_receiver.recv().as_ref(), Some(frame));
+    }
+    assert_eq!(frame_receiver.recv(), None);
+}
+
+#[test]
+fn offline_bitwave() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.child("bitwave.wav");
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|_| rand::random::<u8>())
+        .collect();
+
+    let frame_sender = OfflineSender::<BitWave>::new(&file_path, SAMPLE_RATE);
+    frame_sender.send(&test_data);
+    drop(frame_sender);
+
+    let mut frame_receiver = OfflineReceiver::<BitWave>::new(&file_path);
+    assert_eq!(frame_receiver.recv(), Some(test_data));
+}
+
+#[test]
+fn offline_profiles() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES / 4)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    // Both profiles go through the same build, picked at run time.
+    for profile in [LinkProfile::Cable, LinkProfile::Air] {
+        let file_path = temp_dir.child(format!("{:?}.wav", profile));
+        let options = ModemOptions::default();
+        let create_modem =
+            || AnyModem::with_kind(ModemKind::Ofdm, SAMPLE_RATE, profile, options).unwrap();
+        let detector_config = DetectorConfig::from(profile);
+
+        let preamble_length = detector_config.preamble_length;
+        let frame_sender =
+            OfflineSender::with_modem(&file_path, SAMPLE_RATE, create_modem(), preamble_length)
+                .with_warmup(profile.warmup_bytes());
+        frame_sender.send(&test_data);
+        drop(frame_sender);
+
+        let mut frame_receiver =
+            OfflineReceiver::with_config(&file_path, create_modem(), detector_config);
+        assert_eq!(frame_receiver.recv(), Some(test_data.clone()));
+    }
+}
+
+#[test]
+fn offline_timestamp() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.child("timestamp.wav");
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let frame_sender = OfflineSender::<Ofdm>::new(&file_path, SAMPLE_RATE);
+    (0..TEST_FRAMES).for_each(|_| frame_sender.send(&test_data));
+    drop(frame_sender);
+
+    let modem = Ofdm::new(SAMPLE_RATE);
+    let preamble_length = DetectorConfig::default().preamble_length;
+    let frame_samples: usize = FrameManager::construct(&modem, &test_data)
+        .iter()
+        .map(|packet| preamble_length + modem.modulate(packet).len())
+        .sum();
+    let total_samples = AudioPacket::create_reader(&file_path).read_all().len();
+    let leading_samples = total_samples - frame_samples * TEST_FRAMES;
+
+    let mut frame_receiver = OfflineReceiver::<Ofdm>::new(&file_path);
+    for frame in 0..TEST_FRAMES {
+        let (frame_data, timestamp) = frame_receiver.recv_with_timestamp().unwrap();
+        let expected = leading_samples + frame * frame_samples + preamble_length - 1;
+
+        assert_eq!(frame_data, test_data);
+        assert_eq!(timestamp.sample_index, expected);
+        assert_eq!(timestamp.frame_time, None);
+    }
+}
@@ -1,5 +1,5 @@
 use std::time::Duration;
-use audio_network::audio::{Audio, CreateCallback};
+use audio_network::audio::{Audio, AudioBackend, CreateCallback};
 use audio_network::audio::{AudioPacket, AudioDeactivateFlag};
 
 const TEST_SECONDS: usize = 5;
@@ -9,7 +9,7 @@ const TEST_SECONDS: usize = 5;
 fn part1_ck1() {
     let audio = Audio::new().unwrap();
 
-    let sample_rate = audio.sample_rate.get().unwrap();
+    let sample_rate = audio.sample_rate();
     let audio_input = AudioPacket::create_buffer(sample_rate * TEST_SECONDS);
 
     let capture_callback = CreateCallback::capture(audio_input.clone());
@@ -17,18 +17,18 @@ fn part1_ck1() {
     audio.register(Box::new(capture_callback));
 
     println!("Beginning recording...");
-    audio.activate();
+    audio.activate().unwrap();
 
     std::thread::sleep(Duration::from_secs(TEST_SECONDS as u64));
 
     println!("Restarting and cleaning up...");
     audio.deactivate(AudioDeactivateFlag::CleanRestart);
 
-    let playback_callback = CreateCallback::playback(audio_input, &audio.timetick);
+    let playback_callback = CreateCallback::playback(audio_input);
 
     println!("Beginning playback...");
     audio.register(Box::new(playback_callback));
-    audio.activate();
+    audio.activate().unwrap();
 
     std::thread::sleep(Duration::from_secs(TEST_SECONDS as u64));
 
@@ -41,28 +41,28 @@ fn part1_ck1() {
 fn part1_ck2() {
     let audio = Audio::new().unwrap();
 
-    let sample_rate = audio.sample_rate.get().unwrap();
+    let sample_rate = audio.sample_rate();
     let audio_sample = AudioPacket::create_reader("Sample.wav");
     let audio_input = AudioPacket::create_buffer(sample_rate * TEST_SECONDS);
 
     let capture_callback = CreateCallback::capture(audio_input.clone());
-    let playback_sample_callback = CreateCallback::playback(audio_sample, &audio.timetick);
+    let playback_sample_callback = CreateCallback::playback(audio_sample);
 
     println!("Beginning playback...");
     audio.register(capture_callback);
     audio.register(playback_sample_callback);
-    audio.activate();
+    audio.activate().unwrap();
 
     std::thread::sleep(Duration::from_secs(TEST_SECONDS as u64));
 
     println!("Restarting and cleaning up...");
     audio.deactivate(AudioDeactivateFlag::CleanRestart);
 
-    let playback_buffer_callback = CreateCallback::playback(audio_input, &audio.timetick);
+    let playback_buffer_callback = CreateCallback::playback(audio_input);
 
     println!("Beginning playback...");
     audio.register(playback_buffer_callback);
-    audio.activate();
+    audio.activate().unwrap();
 
     std::thread::sleep(Duration::from_secs(TEST_SECONDS as u64));
 
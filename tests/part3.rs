@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use audio_network::audio::{Audio, AudioDeactivateFlag};
+use audio_network::audio::{Audio, AudioBackend, AudioDeactivateFlag};
 use audio_network::modem::{BitByteConverter, Ofdm, Psk, BitWave};
 use audio_network::node::{ErrorCorrector, Receiver, Sender};
 
@@ -47,9 +47,9 @@ fn part3_ck1_sender() {
 
     let test_data = BitByteConverter::bits_to_bytes(&test_data_bits);
 
-    let frame_sander = Sender::<Psk>::new(&audio);
+    let frame_sander = Sender::<Psk>::new(audio.clone());
     info!("Activating audio client...");
-    audio.activate();
+    audio.activate().unwrap();
 
     frame_sander.send(&test_data);
 
@@ -71,9 +71,9 @@ fn part3_ck1_receiver() {
     let mut input = String::new();
     std::io::stdin().read_line(&mut input).unwrap();
 
-    let frame_receiver = Receiver::<Psk>::new(&audio);
+    let frame_receiver = Receiver::<Psk>::new(audio.clone());
     info!("Activating audio client...");
-    audio.activate();
+    audio.activate().unwrap();
 
     let frame_data = frame_receiver.recv();
     info!("Demodulated data length: {:?}", frame_data.len());
@@ -96,11 +96,11 @@ fn part3_ck1_selfcheck() {
         .collect();
     info!("Test data length: {:?}", test_data.len());
 
-    let frame_sander = Sender::<Ofdm>::new(&audio);
-    let frame_receiver = Receiver::<Ofdm>::new(&audio);
+    let frame_sander = Sender::<Ofdm>::new(audio.clone());
+    let frame_receiver = Receiver::<Ofdm>::new(audio.clone());
 
     info!("Activating audio client...");
-    audio.activate();
+    audio.activate().unwrap();
 
     let test_data_clone = test_data.clone();
     std::thread::spawn(move || {
@@ -128,11 +128,11 @@ fn part4_ck1_selfcheck() {
 
     let encoded_data = ErrorCorrector::encode(&test_data);
 
-    let frame_sander = Sender::<Psk>::new(&audio);
-    let frame_receiver = Receiver::<Psk>::new(&audio);
+    let frame_sander = Sender::<Psk>::new(audio.clone());
+    let frame_receiver = Receiver::<Psk>::new(audio.clone());
 
     info!("Activating audio client...");
-    audio.activate();
+    audio.activate().unwrap();
 
     let encoded_data_clone = encoded_data.clone();
     std::thread::spawn(move || {
@@ -164,11 +164,11 @@ fn part5_ck1_selfcheck() {
 
     let encoded_data = ErrorCorrector::encode(&test_data);
 
-    let frame_sander = Sender::<BitWave>::new(&audio);
-    let frame_receiver = Receiver::<BitWave>::new(&audio);
+    let frame_sander = Sender::<BitWave>::new(audio.clone());
+    let frame_receiver = Receiver::<BitWave>::new(audio.clone());
 
     info!("Activating audio client...");
-    audio.activate();
+    audio.activate().unwrap();
 
     let encoded_data_clone = encoded_data.clone();
     std::thread::spawn(move || {
@@ -0,0 +1,52 @@
+use audio_network::audio::{AudioBackend, AudioDeactivateFlag, PcmFormat, PipeAudio};
+use audio_network::modem::Ofdm;
+use audio_network::node::{Receiver, Sender};
+
+const SAMPLE_RATE: usize = 48000;
+const BUFFER_SIZE: usize = 1024;
+const TEST_SEQUENCE_BYTES: usize = 300;
+
+fn pipe_link(format: PcmFormat) {
+    let (pipe_reader, pipe_writer) = std::io::pipe().unwrap();
+
+    let sender_audio = PipeAudio::new(
+        Box::new(std::io::empty()),
+        Box::new(pipe_writer),
+        format,
+        SAMPLE_RATE,
+        BUFFER_SIZE,
+    );
+    let receiver_audio = PipeAudio::new(
+        Box::new(pipe_reader),
+        Box::new(std::io::sink()),
+        format,
+        SAMPLE_RATE,
+        BUFFER_SIZE,
+    );
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let frame_sander = Sender::<Ofdm>::new(sender_audio.clone());
+    let frame_receiver = Receiver::<Ofdm>::new(receiver_audio.clone());
+    receiver_audio.activate().unwrap();
+    sender_audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    sender_audio.deactivate(AudioDeactivateFlag::Deactivate);
+    receiver_audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+}
+
+#[test]
+fn pipe_s16le() {
+    pipe_link(PcmFormat::S16Le);
+}
+
+#[test]
+fn pipe_f32le() {
+    pipe_link(PcmFormat::F32Le);
+}
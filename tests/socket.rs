@@ -0,0 +1,106 @@
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::time::Duration;
+
+use audio_network::audio::{AudioBackend, AudioDeactivateFlag, PcmFormat, SocketAudio, SocketHub};
+use audio_network::modem::Ofdm;
+use audio_network::node::{Receiver, Sender};
+
+const SAMPLE_RATE: usize = 48000;
+const BUFFER_SIZE: usize = 1024;
+const TEST_SEQUENCE_BYTES: usize = 300;
+
+#[test]
+fn socket_hub() {
+    let hub = Arc::new(SocketHub::bind("127.0.0.1:0", SAMPLE_RATE, BUFFER_SIZE).unwrap());
+    let hub_address = hub.local_addr().unwrap();
+
+    let hub_clone = hub.clone();
+    let hub_thread = std::thread::spawn(move || hub_clone.run().unwrap());
+
+    let sender_audio = SocketAudio::connect(hub_address, SAMPLE_RATE).unwrap();
+    let receiver_audio = SocketAudio::connect(hub_address, SAMPLE_RATE).unwrap();
+
+    let test_data: Vec<_> = (0..TEST_SEQUENCE_BYTES)
+        .map(|index| (index % 256) as u8)
+        .collect();
+
+    let frame_sander = Sender::<Ofdm>::new(sender_audio.clone());
+    let frame_receiver = Receiver::<Ofdm>::new(receiver_audio.clone());
+    receiver_audio.activate().unwrap();
+    sender_audio.activate().unwrap();
+
+    frame_sander.send(&test_data);
+    let frame_data = frame_receiver.recv();
+
+    sender_audio.deactivate(AudioDeactivateFlag::Deactivate);
+    receiver_audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(test_data, frame_data);
+    assert_eq!(receiver_audio.buffer_size(), BUFFER_SIZE);
+
+    hub.stop();
+    hub_thread.join().unwrap();
+}
+
+#[test]
+fn socket_hub_queue() {
+    // Long cycles make sure both blocks land in the same one.
+    const CYCLE_SAMPLES: usize = 4800;
+
+    let hub = Arc::new(SocketHub::bind("127.0.0.1:0", SAMPLE_RATE, CYCLE_SAMPLES).unwrap());
+    let hub_address = hub.local_addr().unwrap();
+
+    let hub_clone = hub.clone();
+    let hub_thread = std::thread::spawn(move || hub_clone.run().unwrap());
+
+    let endpoint = UdpSocket::bind("127.0.0.1:0").unwrap();
+    endpoint.connect(hub_address).unwrap();
+    endpoint
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+
+    for level in [0.25, 0.5] {
+        let mut bytes = Vec::new();
+        PcmFormat::F32Le.encode(&[level; CYCLE_SAMPLES], &mut bytes);
+        endpoint.send(&bytes).unwrap();
+    }
+
+    let mut datagram = vec![0; CYCLE_SAMPLES * 4];
+    let mut block = vec![0.0; CYCLE_SAMPLES];
+    let mut levels = Vec::new();
+    for _ in 0..6 {
+        let length = endpoint.recv(&mut datagram).unwrap();
+        PcmFormat::F32Le.decode(&datagram[..length], &mut block);
+        if block[0] != 0.0 {
+            levels.push(block[0]);
+        }
+    }
+
+    hub.stop();
+    hub_thread.join().unwrap();
+    assert_eq!(levels, [0.25, 0.5]);
+}
+
+#[test]
+fn socket_xruns() {
+    // A stand-in hub that stalls for a few cycles between two blocks.
+    const CYCLE_SAMPLES: usize = 480;
+
+    let hub = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let audio = SocketAudio::connect(hub.local_addr().unwrap(), SAMPLE_RATE).unwrap();
+    audio.activate().unwrap();
+
+    let mut datagram = vec![0; CYCLE_SAMPLES * 4];
+    let (_, endpoint) = hub.recv_from(&mut datagram).unwrap();
+
+    let mut bytes = Vec::new();
+    PcmFormat::F32Le.encode(&[0.0; CYCLE_SAMPLES], &mut bytes);
+    for pause in [0, 0, 60, 0] {
+        std::thread::sleep(Duration::from_millis(pause));
+        hub.send_to(&bytes, endpoint).unwrap();
+    }
+    std::thread::sleep(Duration::from_millis(50));
+
+    audio.deactivate(AudioDeactivateFlag::Deactivate);
+    assert_eq!(audio.xruns(), 1);
+}
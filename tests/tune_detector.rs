@@ -1,8 +1,8 @@
-use audio_network::audio::Audio;
-use audio_network::packet::PreambleSequence;
-use audio_network::modem::BitWave;
+use audio_network::audio::{Audio, AudioBackend};
+use audio_network::modem::{BitWave, Modem};
 use audio_network::node::{Receiver, Sender};
 use audio_network::number::FP;
+use audio_network::packet::{DetectorConfig, PreambleSequence};
 
 const TEST_SEQUENCE_BYTES: usize = 500;
 type TargetModem = BitWave;
@@ -19,21 +19,23 @@ fn tune_detector() {
         .map(|_| rand::random::<u8>())
         .collect();
 
-    let frame_sander = Sender::<TargetModem>::new(&audio);
-    let frame_receiver = Receiver::<TargetModem>::new(&audio);
+    let frame_sander = Sender::<TargetModem>::new(audio.clone());
+    let frame_receiver = Receiver::<TargetModem>::new(audio.clone());
 
     std::thread::spawn(move || {
         frame_sander.send(&test_data);
     });
 
     info!("Activating audio client...");
-    audio.activate();
+    audio.activate().unwrap();
 
     let demodulated_data = frame_receiver.recv();
     info!("Demodulated data bytes: {:?}", demodulated_data.len());
 
-    let sample_rate = audio.sample_rate.get().unwrap();
-    let preamble = PreambleSequence::<TargetModem>::new(sample_rate);
+    let sample_rate = audio.sample_rate();
+    let modem = TargetModem::new(sample_rate);
+    let preamble_length = DetectorConfig::default().preamble_length;
+    let preamble = PreambleSequence::new(&modem, sample_rate, preamble_length);
     let correlation_test = correlate(&frame_receiver.recorded_data.lock().unwrap(), &preamble);
     plot_process_result(&correlation_test);
 }